@@ -13,18 +13,64 @@ mod rundo_struct;
 use literal::LiteralMacro;
 use rundo_struct::RundoStruct;
 use proc_macro::TokenStream;
+use syn::punctuated::Punctuated;
+use syn::synom::Parser;
+
+/// the traits can be implemented for the op by `#[rundo(...)]`, only `Rundo`
+/// is implemented by default, since the field ops may not support others.
+const OPT_IMPLS: [&str; 8] = [
+    "compose",
+    "invert",
+    "transform",
+    "serde",
+    "split",
+    "changed_paths",
+    "json_patch",
+    "describe",
+];
 
 #[proc_macro_attribute]
 pub fn rundo(args: TokenStream, input: TokenStream) -> TokenStream {
     let ast = syn::parse(input).expect("Well, can't parse the code, maybe some syntax error!");
-    impl_rundo_attrs(&ast).into()
+    let impls = Punctuated::<syn::Ident, syn::token::Comma>::parse_terminated
+        .parse(args)
+        .expect("#[rundo] args should be the traits to implement, like #[rundo(compose, invert)]");
+    impl_rundo_attrs(&ast, &impls.into_iter().collect::<Vec<_>>()).into()
 }
 
-fn impl_rundo_attrs(item: &syn::Item) -> quote::Tokens {
+fn impl_rundo_attrs(item: &syn::Item, impls: &[syn::Ident]) -> quote::Tokens {
+    if let Some(unknown) = impls.iter().find(|i| !OPT_IMPLS.contains(&i.as_ref())) {
+        panic!(
+            "#[rundo({})] is unknown, supported are {}",
+            unknown,
+            OPT_IMPLS.join(", ")
+        );
+    }
+    let opt_impl = |name: &str, tokens: quote::Tokens| {
+        if impls.iter().any(|i| i == name) {
+            tokens
+        } else {
+            quote!{}
+        }
+    };
+
     if let &syn::Item::Struct(ref s) = item {
-        let op_def = s.op_struct_def();
+        // `split` clones the op, and a parent struct composes or transforms
+        // it as a clonable `Option`.
+        let clone = impls
+            .iter()
+            .any(|i| i == "compose" || i == "transform" || i == "split");
+        let op_def = s.op_struct_def(clone);
         let struct_def = s.struct_def();
         let impl_rundo = s.impl_rundo();
+        let impl_compose = opt_impl("compose", s.impl_compose());
+        let impl_invert = opt_impl("invert", s.impl_invert());
+        let impl_transform = opt_impl("transform", s.impl_transform());
+        let impl_serde = opt_impl("serde", s.impl_serde());
+        let impl_split = opt_impl("split", s.impl_split());
+        let impl_changed_paths = opt_impl("changed_paths", s.impl_changed_paths());
+        let impl_json_patch = opt_impl("json_patch", s.impl_json_patch());
+        let impl_describe = opt_impl("describe", s.impl_describe());
         let literal_macro = s.literal_macro();
 
         quote! {
@@ -34,6 +80,22 @@ fn impl_rundo_attrs(item: &syn::Item) -> quote::Tokens {
 
             #impl_rundo
 
+            #impl_compose
+
+            #impl_invert
+
+            #impl_transform
+
+            #impl_serde
+
+            #impl_split
+
+            #impl_changed_paths
+
+            #impl_json_patch
+
+            #impl_describe
+
             #literal_macro
         }
     } else {
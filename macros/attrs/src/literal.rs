@@ -16,7 +16,15 @@ impl LiteralMacro for syn::ItemStruct {
             let ident = &field.ident;
             let ty = &field.ty;
             let init_field = |init_expr| {
-                if is_inner_rundo_type(field) {
+                if is_rope_field(field) {
+                    quote!{RopeType::from(#init_expr)}
+                } else if is_string_field(field) {
+                    quote!{StringType::from(#init_expr)}
+                } else if is_delta_field(field) {
+                    quote!{DeltaType::<#ty>::from(#init_expr)}
+                } else if collection_type(field).is_some() {
+                    quote!{From::from(#init_expr)}
+                } else if is_inner_rundo_type(field) {
                     quote!{ValueType::<#ty>::from(#init_expr)}
                 } else {
                     quote!{#init_expr}
@@ -1,7 +1,7 @@
 use quote;
 use rundo_types::IMPLED_RUNDO;
 use syn;
-use syn::Meta::{List, Word};
+use syn::Meta::{List, NameValue, Word};
 use syn::NestedMeta::Meta;
 use syn::punctuated::Punctuated;
 use syn::token::Comma;
@@ -15,8 +15,16 @@ pub fn prefix_ident(ident: &syn::Ident, prefix: &str) -> syn::Ident {
 pub trait RundoStruct {
     fn op_name(&self) -> syn::Ident;
     fn struct_def(&self) -> quote::Tokens;
-    fn op_struct_def(&self) -> quote::Tokens;
+    fn op_struct_def(&self, clone: bool) -> quote::Tokens;
     fn impl_rundo(&self) -> quote::Tokens;
+    fn impl_compose(&self) -> quote::Tokens;
+    fn impl_invert(&self) -> quote::Tokens;
+    fn impl_transform(&self) -> quote::Tokens;
+    fn impl_serde(&self) -> quote::Tokens;
+    fn impl_split(&self) -> quote::Tokens;
+    fn impl_changed_paths(&self) -> quote::Tokens;
+    fn impl_json_patch(&self) -> quote::Tokens;
+    fn impl_describe(&self) -> quote::Tokens;
 }
 
 impl RundoStruct for syn::ItemStruct {
@@ -25,20 +33,29 @@ impl RundoStruct for syn::ItemStruct {
     }
 
     fn struct_def(&self) -> quote::Tokens {
+        let attrs = &self.attrs;
         let vis = &self.vis;
         let name = &self.ident;
         let fields_def = self.fields.fields_def();
         quote! {
+            #(#attrs)*
             #vis struct #name { #fields_def }
         }
     }
 
-    fn op_struct_def(&self) -> quote::Tokens {
+    /// the op derives `Clone` only when `clone` is set, since a custom field's
+    /// op may not be `Clone`.
+    fn op_struct_def(&self, clone: bool) -> quote::Tokens {
         let vis = &self.vis;
         let name = self.op_name();
         let ops_def = self.fields.op_def();
+        let derive = if clone {
+            quote!{ #[derive(Debug, Clone)] }
+        } else {
+            quote!{ #[derive(Debug)] }
+        };
         quote! {
-            #[derive(Debug)]
+            #derive
             #vis struct #name {
                  #ops_def
             }
@@ -83,6 +100,249 @@ impl RundoStruct for syn::ItemStruct {
             }
         }
     }
+
+    fn impl_compose(&self) -> quote::Tokens {
+        let op_name = &self.op_name();
+        let compose_impl = self.fields.compose_method();
+        quote! {
+            impl Compose for #op_name {
+                fn compose(&self, next: &Self) -> Self {
+                    #op_name { #compose_impl }
+                }
+            }
+        }
+    }
+
+    fn impl_invert(&self) -> quote::Tokens {
+        let op_name = &self.op_name();
+        let invert_impl = self.fields.invert_method();
+        quote! {
+            impl Invert for #op_name {
+                fn invert(&self) -> Self {
+                    #op_name { #invert_impl }
+                }
+            }
+        }
+    }
+
+    fn impl_transform(&self) -> quote::Tokens {
+        let op_name = &self.op_name();
+        let transform_impl = self.fields.transform_method();
+        quote! {
+            impl Transform for #op_name {
+                fn transform_by(&self, other: &Self, policy: MergePolicy) -> Option<Self> {
+                    Some(#op_name { #transform_impl })
+                }
+            }
+        }
+    }
+
+    /// ops are serialized as a tuple of the field ops, so they can be stored
+    /// compactly, like in a journal.
+    fn impl_serde(&self) -> quote::Tokens {
+        let op_name = &self.op_name();
+        let fields = self.fields.filter_rundo_skip();
+        let len = fields.len();
+        let serialize_elements = fields
+            .iter()
+            .map(|field| {
+                let ident = &field.ident;
+                quote! { tuple.serialize_element(&self.#ident)?; }
+            })
+            .collect::<Vec<_>>();
+        let next_elements = fields
+            .iter()
+            .enumerate()
+            .map(|(idx, field)| {
+                let ident = &field.ident;
+                quote! {
+                    #ident: seq.next_element()?
+                        .ok_or_else(|| serde::de::Error::invalid_length(#idx, &self))?,
+                }
+            })
+            .collect::<Vec<_>>();
+        let visitor = quote! {
+            struct OpVisitor;
+
+            impl<'de> serde::de::Visitor<'de> for OpVisitor {
+                type Value = #op_name;
+
+                fn expecting(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                    write!(f, "a tuple of {} field ops", #len)
+                }
+
+                fn visit_seq<A>(self, mut seq: A) -> Result<#op_name, A::Error>
+                where
+                    A: serde::de::SeqAccess<'de>,
+                {
+                    Ok(#op_name { #(#next_elements)* })
+                }
+            }
+        };
+        quote! {
+            impl serde::Serialize for #op_name {
+                fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+                where
+                    S: serde::Serializer,
+                {
+                    use serde::ser::SerializeTuple;
+                    let mut tuple = serializer.serialize_tuple(#len)?;
+                    #(#serialize_elements)*
+                    tuple.end()
+                }
+            }
+
+            impl<'de> serde::Deserialize<'de> for #op_name {
+                fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+                where
+                    D: serde::Deserializer<'de>,
+                {
+                    #visitor
+
+                    deserializer.deserialize_tuple(#len, OpVisitor)
+                }
+            }
+        }
+    }
+
+    fn impl_split(&self) -> quote::Tokens {
+        let op_name = &self.op_name();
+        let fields = self.fields.filter_rundo_skip();
+        let idents = fields.iter().map(|field| &field.ident).collect::<Vec<_>>();
+        let idents2 = idents.clone();
+        let arms = fields
+            .iter()
+            .map(|field| {
+                let ident = &field.ident;
+                let name = ident.as_ref().map(|ident| ident.as_ref()).unwrap_or("");
+                if is_nested_field(field) {
+                    quote! {
+                        #name => if let Some(ref op) = self.#ident {
+                            let (selected_op, rest_op) = op.split(&path[1..]);
+                            selected.#ident = selected_op;
+                            rest.#ident = rest_op;
+                        },
+                    }
+                } else {
+                    quote! { #name if path.len() == 1 => selected.#ident = rest.#ident.take(), }
+                }
+            })
+            .collect::<Vec<_>>();
+        let path_arms = fields
+            .iter()
+            .map(|field| {
+                let name = field.ident.as_ref().map(|ident| ident.as_ref()).unwrap_or("");
+                if is_nested_field(field) {
+                    let ty = rundo_type_def(field);
+                    quote! { #name => <<#ty as Rundo>::Op as SplitOp>::has_path(&path[1..]), }
+                } else {
+                    quote! { #name => path.len() == 1, }
+                }
+            })
+            .collect::<Vec<_>>();
+        quote! {
+            impl SplitOp for #op_name {
+                fn split(&self, path: &[&str]) -> (Option<Self>, Option<Self>) {
+                    if path.is_empty() {
+                        return (Some(self.clone()), None);
+                    }
+                    let mut selected = #op_name { #(#idents: None,)* };
+                    let mut rest = self.clone();
+                    match path[0] {
+                        #(#arms)*
+                        _ => {}
+                    }
+                    let changed = |op: #op_name| if #(op.#idents2.is_none())&&* { None } else { Some(op) };
+                    (changed(selected), changed(rest))
+                }
+
+                fn has_path(path: &[&str]) -> bool {
+                    if path.is_empty() {
+                        return true;
+                    }
+                    match path[0] {
+                        #(#path_arms)*
+                        _ => false,
+                    }
+                }
+            }
+        }
+    }
+
+    fn impl_changed_paths(&self) -> quote::Tokens {
+        let op_name = &self.op_name();
+        let fields = self.fields.filter_rundo_skip();
+        let idents = fields.iter().map(|field| &field.ident).collect::<Vec<_>>();
+        let names = idents
+            .iter()
+            .map(|ident| ident.as_ref().map(|ident| ident.as_ref()).unwrap_or(""))
+            .collect::<Vec<_>>();
+        quote! {
+            impl ChangedPaths for #op_name {
+                fn changed_paths(&self) -> Vec<FieldPath> {
+                    let mut paths = vec![];
+                    #(if let Some(ref op) = self.#idents {
+                        paths.extend(op.changed_paths().into_iter().map(|path| path.in_field(#names)));
+                    })*
+                    paths
+                }
+            }
+        }
+    }
+
+    fn impl_json_patch(&self) -> quote::Tokens {
+        let name = &self.ident;
+        let fields = self.fields.filter_rundo_skip();
+        let idents = fields.iter().map(|field| &field.ident).collect::<Vec<_>>();
+        let (idents2, idents3) = (idents.clone(), idents.clone());
+        let names = idents
+            .iter()
+            .map(|ident| ident.as_ref().map(|ident| ident.as_ref()).unwrap_or(""))
+            .collect::<Vec<_>>();
+        let names2 = names.clone();
+        quote! {
+            impl JsonPatch for #name {
+                fn export_patch(&self, op: &Self::Op, path: &str, patch: &mut Vec<PatchOp>) {
+                    #(if let Some(ref op) = op.#idents {
+                        self.#idents2.export_patch(op, &child_pointer(path, #names), patch);
+                    })*
+                }
+
+                fn apply_patch(&mut self, tokens: &[String], op: &PatchOp) -> Result<(), PatchError> {
+                    match tokens.first().map(|token| token.as_str()) {
+                        #(Some(#names2) => self.#idents3.apply_patch(&tokens[1..], op),)*
+                        Some(_) => Err(PatchError::PathNotFound(op.path().to_string())),
+                        None => Err(PatchError::Unsupported(op.path().to_string())),
+                    }
+                }
+            }
+        }
+    }
+
+    fn impl_describe(&self) -> quote::Tokens {
+        let op_name = &self.op_name();
+        let fields = self.fields.filter_rundo_skip();
+        let idents = fields.iter().map(|field| &field.ident).collect::<Vec<_>>();
+        let names = idents
+            .iter()
+            .map(|ident| ident.as_ref().map(|ident| ident.as_ref()).unwrap_or(""))
+            .collect::<Vec<_>>();
+        quote! {
+            impl Describe for #op_name {
+                fn describe_at(&self, path: &FieldPath, lines: &mut Vec<String>) {
+                    #(if let Some(ref op) = self.#idents {
+                        op.describe_at(&path.child_field(#names), lines);
+                    })*
+                }
+            }
+
+            impl ::std::fmt::Display for #op_name {
+                fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                    write!(f, "{}", self.describe().join(", "))
+                }
+            }
+        }
+    }
 }
 
 pub trait RundoFields {
@@ -95,6 +355,9 @@ pub trait RundoFields {
     fn back_method(&self) -> quote::Tokens;
     fn forward_method(&self) -> quote::Tokens;
     fn dirty_method(&self) -> quote::Tokens;
+    fn compose_method(&self) -> quote::Tokens;
+    fn invert_method(&self) -> quote::Tokens;
+    fn transform_method(&self) -> quote::Tokens;
 }
 
 fn rundo_field_metas(field: &Field) -> Vec<Vec<syn::NestedMeta>> {
@@ -112,33 +375,225 @@ fn rundo_field_metas(field: &Field) -> Vec<Vec<syn::NestedMeta>> {
         .collect::<Vec<_>>()
 }
 
-fn is_skip_field(field: &Field) -> bool {
+/// if the field marked by `#[rundo(word)]`
+fn has_word_meta(field: &Field, word: &str) -> bool {
     rundo_field_metas(field).iter().any(|metas| {
         metas.iter().any(|meta| match meta {
-            &Meta(Word(ref word)) => word == "skip",
+            &Meta(Word(ref w)) => w == word,
             _ => false,
         })
     })
 }
 
+fn is_skip_field(field: &Field) -> bool {
+    has_word_meta(field, "skip")
+}
+
+fn type_ident(field: &Field) -> Option<&syn::Ident> {
+    if let syn::Type::Path(syn::TypePath { ref path, .. }) = field.ty {
+        path.segments.last().map(|pair| &pair.into_value().ident)
+    } else {
+        None
+    }
+}
+
+/// the `StrDiff` variant specified by `#[rundo(diff = "...")]`
+fn field_diff(field: &Field) -> Option<syn::Ident> {
+    let diff = rundo_field_metas(field)
+        .iter()
+        .flat_map(|metas| metas.iter())
+        .filter_map(|meta| match meta {
+            &Meta(NameValue(ref nv)) if nv.ident == "diff" => match nv.lit {
+                syn::Lit::Str(ref lit) => Some(lit.value()),
+                _ => panic!("#[rundo(diff)] should be a string like #[rundo(diff = \"line\")]"),
+            },
+            _ => None,
+        })
+        .next();
+
+    diff.map(|diff| {
+        if type_ident(field).map_or(true, |ident| ident != "String") {
+            panic!("#[rundo(diff)] only support String field");
+        }
+        let variant = match diff.as_ref() {
+            "char" => "Char",
+            "word" => "Word",
+            "line" => "Line",
+            "replace" => "Replace",
+            _ => panic!("#[rundo(diff)] should be one of \"char\", \"word\", \"line\" or \"replace\""),
+        };
+        syn::Ident::from(variant)
+    })
+}
+
+/// the compare fn specified by `#[rundo(eq = "...")]`, one of `"exact"`,
+/// `"bitwise"`, `"epsilon(value)"` or a fn path like `"my_eq"`.
+pub fn field_eq(field: &Field) -> Option<quote::Tokens> {
+    let eq = rundo_field_metas(field)
+        .iter()
+        .flat_map(|metas| metas.iter())
+        .filter_map(|meta| match meta {
+            &Meta(NameValue(ref nv)) if nv.ident == "eq" => match nv.lit {
+                syn::Lit::Str(ref lit) => Some(lit.value()),
+                _ => panic!("#[rundo(eq)] should be a string like #[rundo(eq = \"bitwise\")]"),
+            },
+            _ => None,
+        })
+        .next();
+
+    eq.map(|eq| {
+        let compound = match field.ty {
+            syn::Type::Array(_) | syn::Type::Tuple(_) => true,
+            _ => false,
+        };
+        if is_rope_field(field) || is_delta_field(field) || !is_inner_rundo_type(field)
+            || compound || type_ident(field).map_or(true, |ident| ident == "String")
+        {
+            panic!("#[rundo(eq)] only support primitive field");
+        }
+        let ty = &field.ty;
+        let eq = eq.trim();
+        if eq == "exact" {
+            quote!{ <#ty as PartialEq>::eq }
+        } else if eq == "bitwise" {
+            quote!{ bitwise_eq::<#ty> }
+        } else if eq.starts_with("epsilon(") && eq.ends_with(')') {
+            let epsilon: syn::Expr = syn::parse_str(&eq["epsilon(".len()..eq.len() - 1])
+                .expect("#[rundo(eq = \"epsilon(...)\")] should be a number");
+            quote!{ |a: &#ty, b: &#ty| epsilon_eq(a, b, #epsilon) }
+        } else {
+            let path: syn::Path = syn::parse_str(eq)
+                .expect("#[rundo(eq)] should be \"exact\", \"bitwise\", \"epsilon(...)\" or a fn path");
+            quote!{ #path }
+        }
+    })
+}
+
+/// a String field marked by `#[rundo(rope)]` is stored as `RopeType`.
+pub fn is_rope_field(field: &Field) -> bool {
+    if is_skip_field(field) {
+        return false;
+    }
+
+    let rope = has_word_meta(field, "rope");
+    if rope && type_ident(field).map_or(true, |ident| ident != "String") {
+        panic!("#[rundo(rope)] only support String field");
+    }
+    rope
+}
+
+/// a String field not marked by `#[rundo(rope)]` is stored as `StringType`.
+pub fn is_string_field(field: &Field) -> bool {
+    !is_skip_field(field) && !is_rope_field(field)
+        && type_ident(field).map_or(false, |ident| ident == "String")
+}
+
+/// a number field marked by `#[rundo(delta)]` is stored as `DeltaType`.
+pub fn is_delta_field(field: &Field) -> bool {
+    if is_skip_field(field) {
+        return false;
+    }
+
+    let delta = has_word_meta(field, "delta");
+    let numbers = [
+        "i8", "u8", "i16", "u16", "i32", "u32", "i64", "u64", "f32", "f64", "isize", "usize",
+    ];
+    if delta && !type_ident(field).map_or(false, |ident| numbers.contains(&ident.as_ref())) {
+        panic!("#[rundo(delta)] only support number field");
+    }
+    delta
+}
+
+/// the undoable type wraps a std collection field.
+pub fn collection_type(field: &Field) -> Option<quote::Tokens> {
+    if is_skip_field(field) {
+        return None;
+    }
+
+    let segment = match field.ty {
+        syn::Type::Path(syn::TypePath { ref path, .. }) => path.segments.last()?.into_value(),
+        _ => return None,
+    };
+    let elem = || match segment.arguments {
+        syn::PathArguments::AngleBracketed(ref args) => match args.args.first() {
+            Some(pair) => match pair.into_value() {
+                &syn::GenericArgument::Type(ref elem) => elem.clone(),
+                _ => panic!("{} should have an element type", segment.ident),
+            },
+            None => panic!("{} should have an element type", segment.ident),
+        },
+        _ => panic!("{} should have an element type", segment.ident),
+    };
+    let ty = &field.ty;
+    if segment.ident == "HashSet" || segment.ident == "BTreeSet" {
+        Some(quote!{ SetType<#ty> })
+    } else if segment.ident == "VecDeque" {
+        let elem = elem();
+        Some(quote!{ DequeType<#elem> })
+    } else if segment.ident == "Vec" {
+        // only byte buffer is supported now.
+        let elem = elem();
+        if quote!{#elem}.to_string() == "u8" {
+            Some(quote!{ BytesType })
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
 pub fn is_inner_rundo_type(field: &Field) -> bool {
     if is_skip_field(field) {
         return false;
     }
 
-    if let syn::Type::Path(syn::TypePath { ref path, .. }) = field.ty {
-        path.segments.last().map_or(false, |pair| {
-            let tt_id = pair.value().ident.as_ref();
+    match field.ty {
+        // arrays and tuples are compared element-wise, the sizes are limited
+        // by the impls of rundo types, a length given by a const is unknown
+        // here.
+        syn::Type::Array(ref array) => {
+            if let syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Int(ref len),
+                ..
+            }) = array.len
+            {
+                if len.value() < 1 || len.value() > 32 {
+                    panic!("rundo only support array field of 1 to 32 elements");
+                }
+            }
+            true
+        }
+        syn::Type::Tuple(ref tuple) => {
+            if tuple.elems.len() > 12 {
+                panic!("rundo only support tuple field up to 12 elements");
+            }
+            !tuple.elems.is_empty()
+        }
+        _ => type_ident(field).map_or(false, |ident| {
+            let tt_id = ident.as_ref();
             IMPLED_RUNDO.iter().any(|t| t == &tt_id)
-        })
-    } else {
-        false
+        }),
     }
 }
 
+/// a field of another struct or a custom `Rundo` type, not a rundo type.
+pub fn is_nested_field(field: &Field) -> bool {
+    !is_skip_field(field) && !is_rope_field(field) && !is_delta_field(field)
+        && collection_type(field).is_none() && !is_inner_rundo_type(field)
+}
+
 fn rundo_type_def(field: &Field) -> quote::Tokens {
     let ty = &field.ty;
-    if is_inner_rundo_type(field) {
+    if is_rope_field(field) {
+        quote!{RopeType}
+    } else if is_string_field(field) {
+        quote!{StringType}
+    } else if is_delta_field(field) {
+        quote!{DeltaType<#ty>}
+    } else if let Some(collection) = collection_type(field) {
+        collection
+    } else if is_inner_rundo_type(field) {
         quote!{ValueType<#ty>}
     } else {
         quote!{#ty}
@@ -193,7 +648,11 @@ impl RundoFields for Fields {
             .iter()
             .map(|field| {
                 let ident = &field.ident;
-                quote! { #ident: self.#ident.change_op(), }
+                match (field_diff(field), field_eq(field)) {
+                    (Some(diff), _) => quote! { #ident: self.#ident.change_op_by(StrDiff::#diff), },
+                    (_, Some(eq)) => quote! { #ident: self.#ident.change_op_with(#eq), },
+                    _ => quote! { #ident: self.#ident.change_op(), },
+                }
             })
             .collect::<Vec<_>>();
         quote!{ #(#defs)* }
@@ -204,7 +663,10 @@ impl RundoFields for Fields {
             .iter()
             .map(|field| {
                 let ident = &field.ident;
-                quote! { self.#ident.dirty() }
+                match field_eq(field) {
+                    Some(eq) => quote! { self.#ident.dirty_with(#eq) },
+                    None => quote! { self.#ident.dirty() },
+                }
             })
             .collect::<Vec<_>>();
         quote!{ #(#defs) ||* }
@@ -226,9 +688,13 @@ impl RundoFields for Fields {
             .iter()
             .map(|field| {
                 let ident = &field.ident;
+                let apply = match field_eq(field) {
+                    Some(eq) => quote!{ self.#ident.back_with(&op, #eq); },
+                    None => quote!{ self.#ident.back(&op); },
+                };
                 quote! {
                     if let Some(ref op) = op.#ident {
-                        self.#ident.back(&op);
+                        #apply
                     }
                     self.reset();
                 }
@@ -242,9 +708,13 @@ impl RundoFields for Fields {
             .iter()
             .map(|field| {
                 let ident = &field.ident;
+                let apply = match field_eq(field) {
+                    Some(eq) => quote!{ self.#ident.forward_with(&op, #eq); },
+                    None => quote!{ self.#ident.forward(&op); },
+                };
                 quote! {
                     if let Some(ref op) = op.#ident {
-                        self.#ident.forward(&op);
+                        #apply
                     }
                     self.reset();
                 }
@@ -252,4 +722,37 @@ impl RundoFields for Fields {
             .collect::<Vec<_>>();
         quote!{ #(#defs)* }
     }
+
+    fn compose_method(&self) -> quote::Tokens {
+        let defs = self.filter_rundo_skip()
+            .iter()
+            .map(|field| {
+                let ident = &field.ident;
+                quote! { #ident: self.#ident.compose(&next.#ident), }
+            })
+            .collect::<Vec<_>>();
+        quote!{ #(#defs)* }
+    }
+
+    fn invert_method(&self) -> quote::Tokens {
+        let defs = self.filter_rundo_skip()
+            .iter()
+            .map(|field| {
+                let ident = &field.ident;
+                quote! { #ident: self.#ident.invert(), }
+            })
+            .collect::<Vec<_>>();
+        quote!{ #(#defs)* }
+    }
+
+    fn transform_method(&self) -> quote::Tokens {
+        let defs = self.filter_rundo_skip()
+            .iter()
+            .map(|field| {
+                let ident = &field.ident;
+                quote! { #ident: self.#ident.transform_by(&other.#ident, policy)?, }
+            })
+            .collect::<Vec<_>>();
+        quote!{ #(#defs)* }
+    }
 }
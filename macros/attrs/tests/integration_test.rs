@@ -6,14 +6,15 @@ extern crate rundo_types;
 
 use rundo_types::prelude::*;
 use rundo_attrs::rundo;
+use std::collections::{BTreeSet, HashSet, VecDeque};
 
-#[rundo]
+#[rundo(compose, invert, transform, split, changed_paths, json_patch, describe)]
 struct Point {
   a: i32,
   b: i32,
 }
 
-#[rundo]
+#[rundo(compose, invert, transform, split, changed_paths, json_patch, describe)]
 struct Embed {
   point: Point,
   c: f32,
@@ -115,3 +116,448 @@ fn test_visible() {
   assert!(!cmplx.dirty());
   assert!(!cmplx.pub_field.dirty());
 }
+
+#[test]
+fn compose_op() {
+  let mut pt = Point! { a: 1, b: 2 };
+  *pt.a = 5;
+  let op1 = pt.change_op().unwrap();
+  pt.reset();
+  *pt.a = 6;
+  *pt.b = 7;
+  let op2 = pt.change_op().unwrap();
+  pt.reset();
+
+  let op = op1.compose(&op2);
+  pt.back(&op);
+  assert_eq!(*pt.a, 1);
+  assert_eq!(*pt.b, 2);
+  pt.forward(&op);
+  assert_eq!(*pt.a, 6);
+  assert_eq!(*pt.b, 7);
+}
+
+#[test]
+fn invert_op() {
+  let mut embed = Embed! {point: Point!{a:1, b:1}, c: 1.0};
+  *embed.point.a = 2;
+  *embed.c = 2.0;
+  let op = embed.change_op().unwrap();
+  embed.reset();
+
+  embed.forward(&op.invert());
+  assert_eq!(*embed.point.a, 1);
+  assert_eq!(*embed.c, 1.0);
+  assert!(op.invert().point.unwrap().b.is_none());
+}
+
+#[test]
+fn transform_op() {
+  let mut pt = Point! { a: 1, b: 1 };
+  *pt.a = 2;
+  let op_a = pt.change_op().unwrap();
+  pt.back(&op_a);
+  *pt.b = 2;
+  let op_b = pt.change_op().unwrap();
+  pt.back(&op_b);
+  *pt.a = 3;
+  let op_c = pt.change_op().unwrap();
+  pt.back(&op_c);
+
+  pt.forward(&op_b);
+  pt.forward(&op_a.transform(&op_b).unwrap());
+  assert_eq!(*pt.a, 2);
+  assert_eq!(*pt.b, 2);
+
+  // both changed field a
+  assert!(op_c.transform(&op_a).is_none());
+}
+
+#[rundo]
+struct Note {
+  title: String,
+  #[rundo(diff = "line")]
+  content: String,
+}
+
+#[test]
+fn diff_granularity() {
+  let mut note = Note! {
+    title: "hello world".to_string(),
+    content: "hello\nworld\n".to_string()
+  };
+  *note.title = "hello rust".to_string();
+  *note.content = "hello\nrust\n".to_string();
+
+  let op = note.change_op().unwrap();
+  assert_eq!(
+    op.content,
+    Some(vec![StrOP::Chg {
+      idx: 6,
+      from: "world\n".to_string(),
+      to: "rust\n".to_string(),
+    }])
+  );
+  assert_eq!(op.title.as_ref().map(|ops| ops.len()), Some(2));
+
+  note.back(&op);
+  assert_eq!(*note.title, "hello world");
+  assert_eq!(*note.content, "hello\nworld\n");
+}
+
+#[rundo]
+struct Doc {
+  #[rundo(rope)]
+  body: String,
+}
+
+#[test]
+fn rope_field() {
+  let mut doc = Doc! {
+    body: "hello world".to_string()
+  };
+  doc.body.replace_range(6.., "rope");
+  doc.body.push_str("!");
+  assert_eq!(doc.body.to_string(), "hello rope!");
+
+  let op = doc.change_op().unwrap();
+  assert_eq!(
+    op.body,
+    Some(vec![StrOP::Chg {
+      idx: 6,
+      from: "world".to_string(),
+      to: "rope!".to_string(),
+    }])
+  );
+
+  doc.back(&op);
+  assert_eq!(doc.body.to_string(), "hello world");
+  doc.forward(&op);
+  assert_eq!(doc.body.to_string(), "hello rope!");
+}
+
+#[rundo(transform)]
+struct Counter {
+  #[rundo(delta)]
+  count: u32,
+  #[rundo(delta)]
+  offset: f32,
+}
+
+#[test]
+fn delta_field() {
+  let mut local = Counter! { count: 10, offset: 0.5 };
+  let mut remote = Counter! { count: 10, offset: 0.5 };
+  *local.count -= 3;
+  *remote.count += 5;
+  *remote.offset = 1.0;
+  let op = local.change_op().unwrap();
+  let remote_op = remote.change_op().unwrap();
+  assert_eq!(op.count, Some(DeltaOp { delta: 3u32.wrapping_neg() }));
+  local.reset();
+
+  // the concurrent deltas merged.
+  local.forward(&remote_op.transform(&op).unwrap());
+  assert_eq!(*local.count, 12);
+  assert_eq!(*local.offset, 1.0);
+
+  local.back(&op);
+  assert_eq!(*local.count, 15);
+}
+
+fn round_eq(a: &f64, b: &f64) -> bool {
+  a.round() == b.round()
+}
+
+#[rundo]
+struct Gauge {
+  #[rundo(eq = "bitwise")]
+  raw: f32,
+  #[rundo(eq = "epsilon(1e-6)")]
+  level: f64,
+  #[rundo(eq = "round_eq")]
+  rounded: f64,
+}
+
+#[test]
+fn custom_eq() {
+  let mut gauge = Gauge! {
+    raw: std::f32::NAN,
+    level: 0.3,
+    rounded: 1.0
+  };
+  *gauge.raw = std::f32::NAN;
+  *gauge.level = 0.1 + 0.2;
+  *gauge.rounded = 1.2;
+  assert!(!gauge.dirty());
+  assert!(gauge.change_op().is_none());
+
+  *gauge.rounded = 1.6;
+  let op = gauge.change_op().unwrap();
+  assert!(op.raw.is_none());
+  assert!(op.level.is_none());
+  assert!(op.rounded.is_some());
+}
+
+#[rundo]
+struct Profile {
+  tags: HashSet<String>,
+  ids: BTreeSet<u32>,
+  recent: VecDeque<String>,
+}
+
+#[test]
+fn collection_fields() {
+  let mut profile = Profile! {
+    tags: HashSet::new(),
+    ids: vec![1, 2].into_iter().collect::<BTreeSet<_>>(),
+    recent: VecDeque::new()
+  };
+  profile.tags.insert("rust".to_string());
+  profile.ids.remove(&1);
+  profile.recent.push_front("a.rs".to_string());
+  profile.recent.push_front("b.rs".to_string());
+
+  let op = profile.change_op().unwrap();
+  assert_eq!(op.tags, Some(vec![SetOP::Add("rust".to_string())]));
+  assert_eq!(op.ids, Some(vec![SetOP::Remove(1)]));
+  assert_eq!(op.recent.as_ref().map(|ops| ops.len()), Some(2));
+
+  profile.back(&op);
+  assert!(profile.tags.is_empty());
+  assert_eq!(profile.ids.len(), 2);
+  assert!(profile.recent.is_empty());
+  profile.forward(&op);
+  assert_eq!(profile.recent.front(), Some(&"b.rs".to_string()));
+}
+
+#[rundo(changed_paths)]
+struct Shape {
+  pos: [f32; 3],
+  range: (u32, u32),
+}
+
+#[test]
+fn array_tuple_fields() {
+  let mut shape = Shape! {
+    pos: [0.0, 0.0, 0.0],
+    range: (1, 5)
+  };
+  shape.pos[2] = 1.0;
+  shape.range.1 = 8;
+
+  let op = shape.change_op().unwrap();
+  let changed = op.pos.as_ref().map(|elems| elems.iter().map(|&(idx, _)| idx).collect::<Vec<_>>());
+  assert_eq!(changed, Some(vec![2]));
+  assert!(op.range.as_ref().map_or(false, |range| range.0.is_none() && range.1.is_some()));
+
+  shape.back(&op);
+  assert_eq!(*shape.pos, [0.0, 0.0, 0.0]);
+  assert_eq!(*shape.range, (1, 5));
+  shape.forward(&op);
+  assert_eq!(*shape.pos, [0.0, 0.0, 1.0]);
+  assert_eq!(*shape.range, (1, 8));
+}
+
+#[rundo]
+struct Image {
+  name: String,
+  data: Vec<u8>,
+}
+
+#[test]
+fn bytes_field() {
+  let mut image = Image! {
+    name: "logo".to_string(),
+    data: vec![0; 256]
+  };
+  image.data[10] = 1;
+  image.data[200] = 2;
+
+  let op = image.change_op().unwrap();
+  assert_eq!(
+    op.data,
+    Some(vec![
+      BytePatch {
+        offset: 10,
+        removed: vec![0],
+        inserted: vec![1],
+      },
+      BytePatch {
+        offset: 200,
+        removed: vec![0],
+        inserted: vec![2],
+      },
+    ])
+  );
+
+  image.back(&op);
+  assert!(image.data.iter().all(|b| *b == 0));
+  image.reset();
+  image.data.extend_from_slice(&[3, 4]);
+  assert_eq!(image.change_op().unwrap().data.map(|patches| patches.len()), Some(1));
+}
+
+#[test]
+fn split_op() {
+  let mut embed = Embed! {point: Point!{a:1, b:1}, c: 1.0};
+  *embed.point.a = 2;
+  *embed.c = 2.0;
+  let op = embed.change_op().unwrap();
+
+  let (point, rest) = op.split(&["point"]);
+  assert!(point.unwrap().c.is_none());
+  assert!(rest.unwrap().point.is_none());
+
+  let (a, rest) = op.split(&["point", "a"]);
+  assert!(a.unwrap().point.unwrap().b.is_none());
+  assert!(rest.unwrap().point.is_none());
+
+  let (b, rest) = op.split(&["point", "b"]);
+  assert!(b.is_none());
+  assert!(rest.is_some());
+
+  // a leaf field has no sub field.
+  assert!(op.split(&["c", "x"]).0.is_none());
+  assert!(op.split(&[]).1.is_none());
+
+  assert!(OpEmbed::has_path(&["point", "a"]));
+  assert!(OpEmbed::has_path(&["c"]));
+  assert!(!OpEmbed::has_path(&["c", "x"]));
+  assert!(!OpEmbed::has_path(&["point", "z"]));
+  assert!(!OpEmbed::has_path(&["pointt"]));
+}
+
+#[test]
+fn changed_paths() {
+  let mut embed = Embed! {point: Point!{a:1, b:1}, c: 1.0};
+  *embed.point.a = 2;
+  *embed.c = 2.0;
+  let paths = embed.change_op().unwrap().changed_paths();
+  let paths = paths.iter().map(|path| path.to_string()).collect::<Vec<_>>();
+  assert_eq!(paths, vec!["point.a", "c"]);
+
+  let mut shape = Shape! {
+    pos: [0.0, 0.0, 0.0],
+    range: (1, 5)
+  };
+  shape.pos[2] = 1.0;
+  shape.range.0 = 2;
+  let paths = shape.change_op().unwrap().changed_paths();
+  let paths = paths.iter().map(|path| path.to_string()).collect::<Vec<_>>();
+  assert_eq!(paths, vec!["pos[2]", "range[0]"]);
+}
+
+#[test]
+fn json_patch() {
+  let mut embed = Embed! {point: Point!{a:1, b:1}, c: 1.0};
+  *embed.point.a = 2;
+  let op = embed.change_op().unwrap();
+  let patch = to_json_patch(&embed, &op);
+  assert_eq!(
+    patch,
+    vec![PatchOp::Replace {
+      path: "/point/a".to_string(),
+      value: Value::from(2),
+    }]
+  );
+
+  embed.back(&op);
+  embed.reset();
+  let parsed = from_json_patch(&mut embed, &patch).unwrap().unwrap();
+  assert_eq!(*embed.point.a, 1);
+  embed.forward(&parsed);
+  assert_eq!(*embed.point.a, 2);
+}
+
+#[test]
+fn describe_op() {
+  let mut embed = Embed! {point: Point!{a:1, b:1}, c: 1.0};
+  *embed.point.a = 2;
+  *embed.c = 2.0;
+  let op = embed.change_op().unwrap();
+  assert_eq!(op.describe(), vec!["point.a: 1 → 2", "c: 1.0 → 2.0"]);
+  assert_eq!(op.to_string(), "point.a: 1 → 2, c: 1.0 → 2.0");
+}
+
+/// a custom rundo type, its op is a plain struct without `Clone` or any op
+/// traits.
+struct Level {
+  value: i32,
+  origin: Option<i32>,
+}
+
+impl Level {
+  fn set(&mut self, value: i32) {
+    if self.origin.is_none() {
+      self.origin = Some(self.value);
+    }
+    self.value = value;
+  }
+}
+
+#[derive(PartialEq, Debug)]
+struct LevelOp {
+  prev: i32,
+  curr: i32,
+}
+
+impl Rundo for Level {
+  type Op = LevelOp;
+
+  fn dirty(&self) -> bool {
+    self.origin.map_or(false, |ori| ori != self.value)
+  }
+
+  fn change_op(&mut self) -> Option<LevelOp> {
+    match self.origin {
+      Some(ori) if ori != self.value => Some(LevelOp {
+        prev: ori,
+        curr: self.value,
+      }),
+      _ => None,
+    }
+  }
+
+  fn reset(&mut self) {
+    self.origin = None;
+  }
+
+  fn back(&mut self, op: &LevelOp) {
+    self.value = op.prev;
+    self.reset();
+  }
+
+  fn forward(&mut self, op: &LevelOp) {
+    self.value = op.curr;
+    self.reset();
+  }
+}
+
+#[rundo]
+struct Player {
+  name: String,
+  level: Level,
+}
+
+#[test]
+fn custom_rundo_field() {
+  let mut player = Player! {
+    name: "adoo".to_string(),
+    level: Level {
+      value: 1,
+      origin: None
+    }
+  };
+  player.level.set(2);
+  player.name.push_str("!");
+  let op = player.change_op().unwrap();
+  assert_eq!(op.level, Some(LevelOp { prev: 1, curr: 2 }));
+
+  player.back(&op);
+  assert_eq!(player.level.value, 1);
+  assert_eq!(*player.name, "adoo");
+  player.forward(&op);
+  assert_eq!(player.level.value, 2);
+  assert_eq!(*player.name, "adoo!");
+}
@@ -0,0 +1,67 @@
+use bincode;
+use serde::Serialize;
+
+fn clone<T: Clone>(data: &T) -> T {
+    data.clone()
+}
+
+fn encode<T: Serialize>(data: &T) -> bincode::Result<Vec<u8>> {
+    bincode::serialize(data)
+}
+
+/// Copies of data at some positions of history, a far away version can be
+/// restored from the nearest copy, not walk every op from current version.
+pub(crate) struct Checkpoints<T> {
+    /// `(pos, data)`, `data` is the data with the first `pos` ops in history
+    /// applied, sorted by `pos`.
+    list: Vec<(usize, T)>,
+    /// take a checkpoint every `interval` ops, `0` means never.
+    pub(crate) interval: usize,
+    clone: fn(&T) -> T,
+    encode: fn(&T) -> bincode::Result<Vec<u8>>,
+}
+
+impl<T> Checkpoints<T> {
+    pub(crate) fn new() -> Self
+    where
+        T: Clone + Serialize,
+    {
+        Checkpoints {
+            list: vec![],
+            interval: 0,
+            clone: clone::<T>,
+            encode: encode::<T>,
+        }
+    }
+
+    /// keep a copy of `data` at `pos`, and return it serialized.
+    pub(crate) fn take(&mut self, pos: usize, data: &T) -> bincode::Result<Vec<u8>> {
+        self.drop_after(pos);
+        if self.last_pos() != Some(pos) {
+            self.list.push((pos, (self.clone)(data)));
+        }
+        (self.encode)(data)
+    }
+
+    pub(crate) fn last_pos(&self) -> Option<usize> {
+        self.list.last().map(|&(pos, _)| pos)
+    }
+
+    /// the history after `pos` changed, drop the checkpoints after it.
+    pub(crate) fn drop_after(&mut self, pos: usize) {
+        self.list.retain(|&(p, _)| p <= pos);
+    }
+
+    /// the checkpoint nearest to `pos`.
+    pub(crate) fn nearest(&self, pos: usize) -> Option<(usize, &T)> {
+        let distance = |p: usize| if p > pos { p - pos } else { pos - p };
+        self.list
+            .iter()
+            .min_by_key(|&&(p, _)| distance(p))
+            .map(|&(p, ref data)| (p, data))
+    }
+
+    pub(crate) fn copy(&self, data: &T) -> T {
+        (self.clone)(data)
+    }
+}
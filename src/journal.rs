@@ -0,0 +1,124 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use bincode;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use rundo_types::MergePolicy;
+
+/// the 12 bytes of a version `ObjectId`.
+pub(crate) type VerBytes = [u8; 12];
+
+/// A change of workspace history. Every change is appended to the journal as
+/// an entry, and the entries replayed in order will rebuild the same history.
+#[derive(Serialize, Deserialize)]
+pub(crate) enum Entry<Op> {
+    UserOp(VerBytes, Op),
+    RobotOp(Option<VerBytes>, VerBytes, Op),
+    /// a robot op captured from the changes of data, at the cursor.
+    CapturedRobotOp(VerBytes, Op),
+    Undo,
+    Redo,
+    SkipTo(VerBytes),
+    Policy(MergePolicy),
+    /// the serialized data at current version.
+    Snapshot(Vec<u8>),
+    /// a user op in an undo scope.
+    ScopedOp(VerBytes, String, Op),
+    UndoScope(String),
+    RedoScope(String),
+    /// an undo keeps the robot ops.
+    UndoLocal,
+}
+
+fn encode<Op: Serialize>(entry: &Entry<&Op>) -> bincode::Result<Vec<u8>> {
+    bincode::serialize(entry)
+}
+
+pub(crate) fn invalid_data(err: bincode::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// An append-only file of workspace history entries.
+pub(crate) struct Journal<Op> {
+    file: File,
+    encode: fn(&Entry<&Op>) -> bincode::Result<Vec<u8>>,
+    error: Option<io::Error>,
+}
+
+impl<Op> Journal<Op> {
+    /// create a new journal, the file at `path` is truncated if it exists.
+    pub(crate) fn create<P: AsRef<Path>>(path: P) -> io::Result<Self>
+    where
+        Op: Serialize,
+    {
+        Ok(Journal {
+            file: File::create(path)?,
+            encode: encode::<Op>,
+            error: None,
+        })
+    }
+
+    /// open an exist journal, return it with all its entries. A broken entry
+    /// at the end is left by an interrupted write, it's dropped from the file.
+    pub(crate) fn open<P: AsRef<Path>>(path: P) -> io::Result<(Self, Vec<Entry<Op>>)>
+    where
+        Op: Serialize + DeserializeOwned,
+    {
+        let mut file = OpenOptions::new().read(true).append(true).open(path)?;
+        let mut buf = vec![];
+        file.read_to_end(&mut buf)?;
+
+        let mut entries = vec![];
+        let mut rest = &buf[..];
+        while !rest.is_empty() {
+            let remain = rest.len();
+            match bincode::deserialize_from(&mut rest) {
+                Ok(entry) => entries.push(entry),
+                Err(err) => match *err {
+                    bincode::ErrorKind::Io(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                        file.set_len((buf.len() - remain) as u64)?;
+                        break;
+                    }
+                    _ => return Err(invalid_data(err)),
+                },
+            }
+        }
+
+        let journal = Journal {
+            file,
+            encode: encode::<Op>,
+            error: None,
+        };
+        Ok((journal, entries))
+    }
+
+    /// append an entry. Once a write failed, the journal stop working and
+    /// keep the error.
+    pub(crate) fn append(&mut self, entry: &Entry<&Op>) {
+        if self.error.is_some() {
+            return;
+        }
+        let file = &mut self.file;
+        let res = (self.encode)(entry)
+            .map_err(invalid_data)
+            .and_then(|buf| file.write_all(&buf));
+        if let Err(err) = res {
+            self.error = Some(err);
+        }
+    }
+
+    pub(crate) fn append_snapshot(&mut self, data: bincode::Result<Vec<u8>>) {
+        match data {
+            Ok(data) => self.append(&Entry::Snapshot(data)),
+            Err(err) => if self.error.is_none() {
+                self.error = Some(invalid_data(err));
+            },
+        }
+    }
+
+    pub(crate) fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+}
@@ -127,19 +127,28 @@
 #![feature(proc_macro)]
 #![feature(decl_macro)]
 
+extern crate bincode;
 extern crate bson;
 #[doc(include = "../readme.md")]
 #[doc(include = "../docs/quickstart.md")]
 extern crate rundo_attrs;
 extern crate rundo_types;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 
+mod checkpoint;
+mod journal;
+mod scope;
 #[cfg(test)]
 mod test;
 
+pub mod shared;
 pub mod workspace;
 
 pub mod prelude {
     pub use rundo_attrs::*;
+    pub use shared::SharedWorkspace;
     pub use workspace::Workspace;
     pub use rundo_types::prelude::*;
 }
@@ -0,0 +1,175 @@
+use std::collections::{HashMap, HashSet};
+use bson::oid::ObjectId;
+use rundo_types::{Invert, MergePolicy, SplitOp, Transform};
+
+use journal::VerBytes;
+use workspace::{ScopeError, WorkSpaceOp};
+
+type Rebase<Op> = fn(Vec<WorkSpaceOp<Op>>, &[bool], MergePolicy) -> Vec<WorkSpaceOp<Op>>;
+
+fn split<Op: SplitOp>(op: &Op, path: &[&str]) -> (Option<Op>, Option<Op>) {
+    op.split(path)
+}
+
+/// keep the ops marked in `keep`, and rebase them over the inverse of the
+/// dropped ops before them. An op can't be rebased by `policy` is dropped.
+fn rebase<Op: Invert + Transform>(
+    ops: Vec<WorkSpaceOp<Op>>,
+    keep: &[bool],
+    policy: MergePolicy,
+) -> Vec<WorkSpaceOp<Op>> {
+    // the inverse of the dropped ops, which turn the data of the walked ops
+    // to the data only the kept ops applied.
+    let mut back: Vec<Op> = vec![];
+    let mut kept = vec![];
+    for (e, &keep) in ops.into_iter().zip(keep) {
+        if keep {
+            let mut rebased: Option<Op> = None;
+            let mut new_back = Vec::with_capacity(back.len());
+            for inv in &back {
+                let pair = {
+                    let op = rebased.as_ref().unwrap_or_else(|| e.op());
+                    (
+                        op.transform_by(inv, policy),
+                        inv.transform_by(op, policy.reverse()),
+                    )
+                };
+                match pair {
+                    (Some(op), Some(inv)) => {
+                        rebased = Some(op);
+                        new_back.push(inv);
+                    }
+                    _ => break,
+                }
+            }
+            if new_back.len() == back.len() {
+                back = new_back;
+                kept.push(match rebased {
+                    Some(op) => e.with_op(op),
+                    None => e,
+                });
+                continue;
+            }
+        }
+        back.insert(0, e.op().invert());
+    }
+    kept
+}
+
+/// Undo scopes of a workspace. A scope is a field path of data, the changes
+/// in it are recorded as separate user ops, and can be undone alone.
+pub(crate) struct Scopes<Op> {
+    /// the registered scopes, dotted field paths like `"canvas.shapes"`.
+    paths: Vec<String>,
+    split: Option<fn(&Op, &[&str]) -> (Option<Op>, Option<Op>)>,
+    rebase: Option<Rebase<Op>>,
+    /// the scope of user ops by version, an op not in any scope is not here.
+    tags: HashMap<VerBytes, String>,
+    /// the redo ops of other scopes kept by a new op, only their scopes can
+    /// redo them.
+    kept: HashSet<VerBytes>,
+}
+
+impl<Op> Scopes<Op> {
+    pub(crate) fn new() -> Self {
+        Scopes {
+            paths: vec![],
+            split: None,
+            rebase: None,
+            tags: HashMap::new(),
+            kept: HashSet::new(),
+        }
+    }
+
+    pub(crate) fn add(&mut self, path: &str) -> Result<(), ScopeError>
+    where
+        Op: SplitOp + Invert + Transform,
+    {
+        if path.is_empty() || !Op::has_path(&path.split('.').collect::<Vec<_>>()) {
+            return Err(ScopeError::PathNotFound);
+        }
+        let overlap = |a: &str, b: &str| a == b || a.starts_with(&format!("{}.", b));
+        if self.paths
+            .iter()
+            .any(|p| overlap(p, path) || overlap(path, p))
+        {
+            return Err(ScopeError::Overlap);
+        }
+        self.paths.push(path.to_string());
+        self.split = Some(split::<Op>);
+        self.rebase = Some(rebase::<Op>);
+        Ok(())
+    }
+
+    /// rebase the kept redo ops of a replayed journal, its scopes may not be
+    /// registered yet.
+    pub(crate) fn enable_rebase(&mut self)
+    where
+        Op: Invert + Transform,
+    {
+        self.rebase = Some(rebase::<Op>);
+    }
+
+    /// split `op` to the parts of every scope, the changes not in any scope
+    /// are the last part without a scope.
+    pub(crate) fn split_op(&self, op: Op) -> Vec<(Option<String>, Op)> {
+        let split = match self.split {
+            Some(split) => split,
+            None => return vec![(None, op)],
+        };
+        let mut parts = vec![];
+        let mut rest = Some(op);
+        for path in &self.paths {
+            let (selected, others) = match rest {
+                Some(ref op) => split(op, &path.split('.').collect::<Vec<_>>()),
+                None => break,
+            };
+            if let Some(selected) = selected {
+                parts.push((Some(path.clone()), selected));
+            }
+            rest = others;
+        }
+        parts.extend(rest.map(|op| (None, op)));
+        parts
+    }
+
+    pub(crate) fn tag(&mut self, ver: &ObjectId, scope: String) {
+        self.tags.insert(ver.bytes(), scope);
+    }
+
+    pub(crate) fn scope_of(&self, ver: &ObjectId) -> Option<&str> {
+        self.tags.get(&ver.bytes()).map(|scope| scope.as_str())
+    }
+
+    /// the redo ops kept by a new op of `scope`, which are the user ops of
+    /// other scopes. They are rebased over the inverse of the dropped ops, an
+    /// op can't be rebased is dropped too.
+    pub(crate) fn keep_redo(
+        &mut self,
+        redo_ops: Vec<WorkSpaceOp<Op>>,
+        scope: Option<&str>,
+        policy: MergePolicy,
+    ) -> Vec<WorkSpaceOp<Op>> {
+        let keep = redo_ops
+            .iter()
+            .map(|e| e.is_user_op() && self.scope_of(e.version()) != scope)
+            .collect::<Vec<_>>();
+        let kept = match self.rebase {
+            Some(rebase) if keep.contains(&true) => rebase(redo_ops, &keep, policy),
+            _ => vec![],
+        };
+        for e in &kept {
+            self.kept.insert(e.version().bytes());
+        }
+        kept
+    }
+
+    /// the op is redone by its scope, it's not a kept op anymore.
+    pub(crate) fn unkeep(&mut self, ver: &ObjectId) {
+        self.kept.remove(&ver.bytes());
+    }
+
+    pub(crate) fn is_kept(&self, ver: &ObjectId) -> bool {
+        self.kept.contains(&ver.bytes())
+    }
+}
@@ -0,0 +1,203 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use rundo_types::{Invert, Rundo, Transform};
+use workspace::{OpError, Workspace};
+
+struct Shared<T: Rundo + 'static> {
+    ws: Mutex<Workspace<T>>,
+    /// notified when there is no batch in the workspace.
+    idle: Condvar,
+}
+
+/// A workspace can be shared between threads, clone it to share the same
+/// workspace. Every access locks the whole workspace.
+///
+/// It's `Send` and `Sync` if the data and its op are `Send`. The ops
+/// generated by `#[rundo]` are `Send` and `Sync` if all the field ops are, and
+/// all the ops of rundo types are, if their values are.
+///
+/// A user op is the same as `Workspace`, the changes during a `get_mut` guard
+/// or between `begin_op` and `end_op` are one op, even they are from
+/// different threads. The changes through `robot_mut`, like from a background
+/// task, are recorded as robot ops, which can't be undone and are kept by
+/// `undo_local`.
+pub struct SharedWorkspace<T: Rundo + 'static> {
+    inner: Arc<Shared<T>>,
+}
+
+impl<T: Rundo> Clone for SharedWorkspace<T> {
+    fn clone(&self) -> Self {
+        SharedWorkspace {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: Rundo> From<Workspace<T>> for SharedWorkspace<T> {
+    fn from(ws: Workspace<T>) -> Self {
+        SharedWorkspace {
+            inner: Arc::new(Shared {
+                ws: Mutex::new(ws),
+                idle: Condvar::new(),
+            }),
+        }
+    }
+}
+
+impl<T: Rundo> SharedWorkspace<T> {
+    pub fn new(data: T) -> Self {
+        SharedWorkspace::from(Workspace::new(data))
+    }
+
+    /// lock the workspace to use it directly, other threads will be blocked
+    /// until the lock dropped.
+    pub fn lock(&self) -> WorkspaceLock<T> {
+        WorkspaceLock {
+            ws: self.inner.ws.lock().expect("rundo workspace lock poisoned"),
+            idle: &self.inner.idle,
+        }
+    }
+
+    /// like `Workspace::get_mut`, the changes during the guard lifetime are
+    /// captured as a user op.
+    pub fn get_mut(&self) -> SharedRefGuard<T> {
+        let mut lock = self.lock();
+        lock.begin_op();
+        SharedRefGuard { lock }
+    }
+
+    /// the changes during the guard lifetime are captured as a robot op. It
+    /// waits until the current batch finished, so never call it between
+    /// `begin_op` and `end_op` in the same thread, it will never return.
+    pub fn robot_mut(&self) -> RobotRefGuard<T>
+    where
+        T::Op: Transform,
+    {
+        let mut ws = self.inner.ws.lock().expect("rundo workspace lock poisoned");
+        while ws.batch > 0 {
+            ws = self.inner
+                .idle
+                .wait(ws)
+                .expect("rundo workspace lock poisoned");
+        }
+        RobotRefGuard {
+            lock: WorkspaceLock {
+                ws,
+                idle: &self.inner.idle,
+            },
+        }
+    }
+
+    pub fn begin_op(&self) {
+        self.lock().begin_op();
+    }
+
+    pub fn end_op(&self) {
+        self.lock().end_op();
+    }
+
+    pub fn undo(&self) -> Option<usize> {
+        self.lock().undo()
+    }
+
+    /// like `Workspace::undo_local`, undo the last user op but keep the robot
+    /// ops after it.
+    pub fn undo_local(&self) -> Result<Option<usize>, OpError>
+    where
+        T::Op: Invert + Transform,
+    {
+        self.lock().undo_local()
+    }
+
+    pub fn redo(&self) -> Option<usize> {
+        self.lock().redo()
+    }
+}
+
+/// The locked workspace.
+pub struct WorkspaceLock<'a, T: Rundo + 'static> {
+    ws: MutexGuard<'a, Workspace<T>>,
+    idle: &'a Condvar,
+}
+
+impl<'a, T: Rundo> Drop for WorkspaceLock<'a, T> {
+    fn drop(&mut self) {
+        if self.ws.batch == 0 {
+            self.idle.notify_all();
+        }
+    }
+}
+
+impl<'a, T: Rundo> Deref for WorkspaceLock<'a, T> {
+    type Target = Workspace<T>;
+    fn deref(&self) -> &Workspace<T> {
+        &self.ws
+    }
+}
+
+impl<'a, T: Rundo> DerefMut for WorkspaceLock<'a, T> {
+    fn deref_mut(&mut self) -> &mut Workspace<T> {
+        &mut self.ws
+    }
+}
+
+/// SharedRefGuard keeps the workspace locked and record a user op when dropped.
+pub struct SharedRefGuard<'a, T: Rundo + 'static> {
+    lock: WorkspaceLock<'a, T>,
+}
+
+impl<'a, T: Rundo> Drop for SharedRefGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.end_op();
+    }
+}
+
+impl<'a, T: Rundo> Deref for SharedRefGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.lock.data
+    }
+}
+
+impl<'a, T: Rundo> DerefMut for SharedRefGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.lock.data
+    }
+}
+
+/// RobotRefGuard keeps the workspace locked and record a robot op when dropped.
+pub struct RobotRefGuard<'a, T: Rundo + 'static>
+where
+    T::Op: Transform,
+{
+    lock: WorkspaceLock<'a, T>,
+}
+
+impl<'a, T: Rundo> Drop for RobotRefGuard<'a, T>
+where
+    T::Op: Transform,
+{
+    fn drop(&mut self) {
+        self.lock.capture_robot_op();
+    }
+}
+
+impl<'a, T: Rundo> Deref for RobotRefGuard<'a, T>
+where
+    T::Op: Transform,
+{
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.lock.data
+    }
+}
+
+impl<'a, T: Rundo> DerefMut for RobotRefGuard<'a, T>
+where
+    T::Op: Transform,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.lock.data
+    }
+}
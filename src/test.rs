@@ -5,14 +5,34 @@
 mod test {
     use rundo_types::prelude::*;
     use rundo_attrs::rundo;
-    use workspace::Workspace;
-
-    #[rundo]
+    use bson::oid::ObjectId;
+    use std::env;
+    use std::fs::{self, OpenOptions};
+    use std::path::PathBuf;
+    use std::thread;
+    use shared::SharedWorkspace;
+    use workspace::{OpError, ScopeError, Workspace};
+
+    #[rundo(compose, invert, transform, serde, split, changed_paths, json_patch, describe)]
+    #[derive(Clone, Serialize, Deserialize)]
     struct Point {
         x: f32,
         y: f32,
     }
 
+    #[rundo(describe)]
+    #[derive(Clone, Serialize, Deserialize)]
+    struct Note {
+        text: String,
+    }
+
+    #[rundo(invert, transform, serde)]
+    #[derive(Clone, Serialize, Deserialize)]
+    struct Gauge {
+        #[rundo(eq = "bitwise")]
+        raw: f32,
+    }
+
     type Space = Workspace<Point>;
     fn new_space() -> Space {
         Workspace::new(Point! { x: 0.0, y: 0.0 })
@@ -216,4 +236,651 @@ mod test {
         ws.undo();
         assert_eq!(&v1, ws.top_ver().unwrap());
     }
+
+    #[test]
+    fn history() {
+        let mut ws = new_space();
+        action_modify(&mut ws, 1.0, 1.0);
+        let v1 = ws.top_ver().unwrap().clone();
+        action_modify(&mut ws, 2.0, 2.0);
+        let v2 = ws.top_ver().unwrap().clone();
+        action_modify(&mut ws, 3.0, 3.0);
+        ws.undo();
+
+        let history = ws.history();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.cursor(), 2);
+
+        let entries = history.collect::<Vec<_>>();
+        assert_eq!(entries[0].version(), &v1);
+        assert_eq!(entries[1].version(), &v2);
+        assert!(entries.iter().all(|e| e.is_user_op()));
+        assert!(entries[0].is_applied() && !entries[0].is_top());
+        assert!(entries[1].is_applied() && entries[1].is_top());
+        assert!(!entries[2].is_applied() && !entries[2].is_top());
+        assert_eq!(entries[2].index(), 2);
+
+        let redo = ws.history().rev().next().unwrap();
+        assert!(redo.op().x.is_some());
+    }
+
+    #[test]
+    fn snapshot_at() {
+        let mut ws = new_space();
+        action_modify(&mut ws, 1.0, 1.0);
+        let v1 = ws.top_ver().unwrap().clone();
+        action_modify(&mut ws, 2.0, 2.0);
+        let v2 = ws.top_ver().unwrap().clone();
+        action_modify(&mut ws, 3.0, 3.0);
+        let v3 = ws.top_ver().unwrap().clone();
+        ws.undo();
+
+        let snapshot = ws.snapshot_at(&v1).unwrap();
+        assert_eq!(*snapshot.x, 1.0);
+        let snapshot = ws.snapshot_at(&v3).unwrap();
+        assert_eq!(*snapshot.y, 3.0);
+        let snapshot = ws.snapshot_at(&v2).unwrap();
+        assert_eq!(*snapshot.x, 2.0);
+
+        // uncaptured changes don't leak into snapshot.
+        *ws.data.x = 10.0;
+        let snapshot = ws.snapshot_at(&v1).unwrap();
+        assert_eq!(*snapshot.x, 1.0);
+
+        // workspace is untouched
+        assert_eq!(*ws.data.x, 10.0);
+        assert_eq!(ws.top_ver(), Some(&v2));
+    }
+
+    #[test]
+    fn diff() {
+        let mut ws = new_space();
+        action_modify(&mut ws, 1.0, 1.0);
+        let v1 = ws.top_ver().unwrap().clone();
+        action_modify(&mut ws, 2.0, 5.0);
+        action_modify(&mut ws, 3.0, 5.0);
+        let v3 = ws.top_ver().unwrap().clone();
+
+        let op = ws.diff(&v1, &v3).unwrap();
+        ws.undo_to(&v1);
+        ws.data.forward(&op);
+        assert_eq!(*ws.data.x, 3.0);
+        assert_eq!(*ws.data.y, 5.0);
+        ws.data.back(&op);
+        assert_eq!(*ws.data.x, 1.0);
+        assert_eq!(*ws.data.y, 1.0);
+
+        ws.redo_to(&v3);
+        let op = ws.diff(&v3, &v1).unwrap();
+        ws.data.forward(&op);
+        assert_eq!(*ws.data.x, 1.0);
+        assert_eq!(*ws.data.y, 1.0);
+
+        assert!(ws.diff(&v1, &v1).is_none());
+    }
+
+    #[test]
+    fn undo_op() {
+        let mut ws = new_space();
+        *ws.get_mut().x = 1.0;
+        let v1 = ws.top_ver().unwrap().clone();
+        *ws.get_mut().y = 2.0;
+        let v2 = ws.top_ver().unwrap().clone();
+        *ws.get_mut().y = 3.0;
+
+        // x is not touched by later ops.
+        let v4 = ws.undo_op(&v1).unwrap();
+        assert_eq!(*ws.data.x, 0.0);
+        assert_eq!(*ws.data.y, 3.0);
+        assert_eq!(ws.ops_len(), 4);
+        assert_eq!(ws.top_ver(), Some(&v4));
+
+        // y changed by later op.
+        assert_eq!(ws.undo_op(&v2), Err(OpError::Conflict));
+        assert_eq!(*ws.data.y, 3.0);
+        assert_eq!(ws.ops_len(), 4);
+
+        // the revert itself can be undo.
+        ws.undo();
+        assert_eq!(*ws.data.x, 1.0);
+        assert_eq!(ws.undo_op(&v4), Err(OpError::NotApplied));
+    }
+
+    fn remote_op(x: f32, y: f32, to_x: f32, to_y: f32) -> OpPoint {
+        let mut remote = Point! { x: x, y: y };
+        *remote.x = to_x;
+        *remote.y = to_y;
+        remote.change_op().unwrap()
+    }
+
+    #[test]
+    fn apply_robot_op() {
+        let mut ws = new_space();
+        *ws.get_mut().x = 1.0;
+        let v1 = ws.top_ver().unwrap().clone();
+        *ws.get_mut().y = 2.0;
+        let v2 = ws.top_ver().unwrap().clone();
+
+        // server changed x base on v1.
+        let r1 = ObjectId::new().unwrap();
+        ws.apply_robot_op(Some(&v1), r1.clone(), remote_op(1.0, 0.0, 5.0, 0.0))
+            .unwrap();
+        assert_eq!(*ws.data.x, 5.0);
+        assert_eq!(*ws.data.y, 2.0);
+        let versions = ws.history().map(|e| e.version().clone()).collect::<Vec<_>>();
+        assert_eq!(versions, vec![v1.clone(), r1.clone(), v2.clone()]);
+        assert_eq!(ws.robot_ops_len(), 1);
+
+        // local y wins by default.
+        let r2 = ObjectId::new().unwrap();
+        ws.apply_robot_op(Some(&r1), r2.clone(), remote_op(5.0, 0.0, 5.0, 7.0))
+            .unwrap();
+        assert_eq!(*ws.data.y, 2.0);
+
+        // the rebased local op still can be undo and redo.
+        ws.undo();
+        assert_eq!(*ws.data.y, 7.0);
+        assert_eq!(*ws.data.x, 5.0);
+        ws.redo();
+        assert_eq!(*ws.data.y, 2.0);
+
+        ws.set_merge_policy(MergePolicy::Conflict);
+        let r3 = ObjectId::new().unwrap();
+        let res = ws.apply_robot_op(Some(&r2), r3.clone(), remote_op(5.0, 7.0, 5.0, 8.0));
+        assert_eq!(res, Err(OpError::Conflict));
+        assert_eq!(ws.history().len(), 4);
+        assert_eq!(*ws.data.y, 2.0);
+
+        ws.set_merge_policy(MergePolicy::FirstWriterWins);
+        ws.apply_robot_op(Some(&r2), r3.clone(), remote_op(5.0, 7.0, 5.0, 8.0))
+            .unwrap();
+        assert_eq!(*ws.data.y, 8.0);
+
+        let unknown = ObjectId::new().unwrap();
+        let res = ws.apply_robot_op(Some(&unknown), unknown.clone(), remote_op(5.0, 8.0, 1.0, 8.0));
+        assert_eq!(res, Err(OpError::VersionNotFound));
+    }
+
+    #[test]
+    fn undo_keep_robot_ops() {
+        let mut ws = new_space();
+        *ws.get_mut().x = 1.0;
+        let v1 = ws.top_ver().unwrap().clone();
+        let r1 = ObjectId::new().unwrap();
+        ws.apply_robot_op(Some(&v1), r1.clone(), remote_op(1.0, 0.0, 1.0, 7.0))
+            .unwrap();
+
+        // `undo` reverts the robot op too.
+        ws.undo();
+        assert_eq!(*ws.data.x, 0.0);
+        assert_eq!(*ws.data.y, 0.0);
+        ws.redo_to(&r1);
+
+        assert_eq!(ws.undo_local(), Ok(Some(0)));
+        assert_eq!(*ws.data.x, 0.0);
+        assert_eq!(*ws.data.y, 7.0);
+        let versions = ws.history().map(|e| e.version().clone()).collect::<Vec<_>>();
+        assert_eq!(versions, vec![r1.clone(), v1.clone()]);
+        assert_eq!(ws.top_ver(), Some(&r1));
+
+        ws.redo();
+        assert_eq!(*ws.data.x, 1.0);
+        assert_eq!(*ws.data.y, 7.0);
+
+        // robot changed the same field, its change survive.
+        let r2 = ObjectId::new().unwrap();
+        ws.apply_robot_op(Some(&v1), r2, remote_op(1.0, 7.0, 9.0, 7.0))
+            .unwrap();
+        ws.undo_local().unwrap();
+        assert_eq!(*ws.data.x, 9.0);
+        ws.redo();
+        assert_eq!(*ws.data.x, 9.0);
+        assert_eq!(*ws.data.y, 7.0);
+
+        ws.undo_local().unwrap();
+        assert_eq!(ws.undo_local(), Ok(None));
+    }
+
+    #[test]
+    fn ops_len_with_robot_ops() {
+        let mut ws = new_space();
+        *ws.data.x = 1.0;
+        let r1 = ws.capture_robot_op().unwrap();
+        *ws.data.y = 2.0;
+        ws.capture_robot_op();
+        *ws.get_mut().x = 3.0;
+        assert_eq!(ws.ops_len(), 1);
+        assert_eq!(ws.robot_ops_len(), 2);
+
+        ws.undo_to(&r1);
+        assert_eq!(*ws.data.x, 1.0);
+        assert_eq!(*ws.data.y, 0.0);
+        assert_eq!(ws.ops_len(), 0);
+        assert_eq!(ws.robot_ops_len(), 1);
+
+        ws.redo();
+        assert_eq!(*ws.data.x, 3.0);
+        assert_eq!(ws.ops_len(), 1);
+        assert_eq!(ws.robot_ops_len(), 2);
+    }
+
+    fn journal_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("rundo_{}_{}.journal", name, ::std::process::id()))
+    }
+
+    fn versions(ws: &Space) -> Vec<ObjectId> {
+        ws.history().map(|e| e.version().clone()).collect()
+    }
+
+    #[test]
+    fn journal_recover() {
+        let path = journal_path("recover");
+        let mut ws = Workspace::with_journal(Point! { x: 0.0, y: 0.0 }, &path).unwrap();
+        *ws.get_mut().x = 1.0;
+        let v1 = ws.top_ver().unwrap().clone();
+        action_modify(&mut ws, 2.0, 3.0);
+        let r1 = ObjectId::new().unwrap();
+        ws.apply_robot_op(Some(&v1), r1.clone(), remote_op(1.0, 0.0, 1.0, 5.0))
+            .unwrap();
+        *ws.get_mut().x = 4.0;
+        ws.undo();
+        ws.undo();
+        ws.redo();
+
+        let recovered = Workspace::recover(Point! { x: 0.0, y: 0.0 }, &path).unwrap();
+        assert_eq!(*recovered.data.x, *ws.data.x);
+        assert_eq!(*recovered.data.y, *ws.data.y);
+        assert_eq!(versions(&recovered), versions(&ws));
+        assert_eq!(recovered.top_ver(), ws.top_ver());
+        assert_eq!(recovered.ops_len(), ws.ops_len());
+
+        // the recovered workspace keeps journaling, and can undo the old ops.
+        let mut ws = recovered;
+        ws.undo();
+        ws.undo_to(&v1);
+        assert_eq!(*ws.data.x, 1.0);
+        let recovered = Workspace::recover(Point! { x: 0.0, y: 0.0 }, &path).unwrap();
+        assert_eq!(*recovered.data.x, 1.0);
+        assert_eq!(*recovered.data.y, 0.0);
+        assert_eq!(recovered.top_ver(), Some(&v1));
+        assert!(ws.journal_error().is_none());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn journal_broken_tail() {
+        let path = journal_path("broken_tail");
+        let mut ws = Workspace::with_journal(Point! { x: 0.0, y: 0.0 }, &path).unwrap();
+        action_modify(&mut ws, 1.0, 2.0);
+        let len = fs::metadata(&path).unwrap().len();
+        action_modify(&mut ws, 3.0, 4.0);
+
+        // the last entry is only half written.
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len + 3).unwrap();
+        let mut ws = Workspace::recover(Point! { x: 0.0, y: 0.0 }, &path).unwrap();
+        assert_eq!(*ws.data.x, 1.0);
+        assert_eq!(ws.ops_len(), 1);
+
+        action_modify(&mut ws, 5.0, 6.0);
+        let ws = Workspace::recover(Point! { x: 0.0, y: 0.0 }, &path).unwrap();
+        assert_eq!(*ws.data.x, 5.0);
+        assert_eq!(ws.ops_len(), 2);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn checkpoint_skip_to() {
+        let mut ws = new_space();
+        ws.set_checkpoint_interval(2);
+        let vers = (1..6)
+            .map(|i| {
+                *ws.get_mut().x = i as f32;
+                ws.top_ver().unwrap().clone()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ws.checkpoints.as_ref().unwrap().last_pos(), Some(4));
+
+        ws.skip_to(&vers[0]);
+        assert_eq!(*ws.data.x, 1.0);
+        assert_eq!(ws.top_ver(), Some(&vers[0]));
+        ws.redo();
+        assert_eq!(*ws.data.x, 2.0);
+        ws.skip_to(&vers[4]);
+        assert_eq!(*ws.data.x, 5.0);
+
+        // a new op after undo drops the checkpoints of the old ops.
+        ws.skip_to(&vers[1]);
+        *ws.get_mut().x = 9.0;
+        assert_eq!(ws.checkpoints.as_ref().unwrap().last_pos(), Some(2));
+        ws.skip_to(&vers[0]);
+        assert_eq!(*ws.data.x, 1.0);
+        ws.redo();
+        ws.redo();
+        assert_eq!(*ws.data.x, 9.0);
+    }
+
+    #[test]
+    fn checkpoint_skip_over_robot_ops() {
+        let mut ws = new_space();
+        ws.set_checkpoint_interval(2);
+        *ws.get_mut().x = 1.0;
+        let v1 = ws.top_ver().unwrap().clone();
+        *ws.get_mut().x = 2.0;
+        *ws.data.y = 5.0;
+        ws.capture_robot_op();
+        *ws.get_mut().x = 3.0;
+        *ws.get_mut().x = 4.0;
+        assert_eq!(ws.ops_len(), 4);
+
+        ws.skip_to(&v1);
+        assert_eq!(*ws.data.x, 1.0);
+        assert_eq!(*ws.data.y, 0.0);
+        assert_eq!(ws.ops_len(), 1);
+        assert_eq!(ws.robot_ops_len(), 0);
+    }
+
+    #[test]
+    fn journal_load() {
+        let path = journal_path("load");
+        let mut ws = Workspace::with_journal(Point! { x: 0.0, y: 0.0 }, &path).unwrap();
+        ws.set_checkpoint_interval(2);
+        action_modify(&mut ws, 1.0, 1.0);
+        let v1 = ws.top_ver().unwrap().clone();
+        action_modify(&mut ws, 2.0, 2.0);
+        action_modify(&mut ws, 3.0, 3.0);
+        ws.undo();
+        *ws.get_mut().y = 4.0;
+
+        // the ops before the snapshot are not applied to data, so even the
+        // initial data is different, the data is loaded from the snapshot.
+        let mut loaded = Workspace::load(Point! { x: 7.0, y: 7.0 }, &path).unwrap();
+        assert_eq!(*loaded.data.x, 2.0);
+        assert_eq!(*loaded.data.y, 4.0);
+        assert_eq!(versions(&loaded), versions(&ws));
+        assert_eq!(loaded.top_ver(), ws.top_ver());
+
+        // the ops before the snapshot still can be undone.
+        loaded.undo_to(&v1);
+        assert_eq!(*loaded.data.x, 1.0);
+        assert_eq!(*loaded.data.y, 1.0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn journal_load_custom_eq() {
+        let path = journal_path("load_custom_eq");
+        let mut ws = Workspace::with_journal(Gauge! { raw: 0.0 }, &path).unwrap();
+        ws.set_checkpoint_interval(1);
+        *ws.get_mut().raw = ::std::f32::NAN;
+        assert_eq!(ws.ops_len(), 1);
+
+        // the loaded field still compares bitwise, assign NaN again is not a
+        // change.
+        let mut loaded = Workspace::load(Gauge! { raw: 0.0 }, &path).unwrap();
+        assert!(loaded.data.raw.is_nan());
+        *loaded.get_mut().raw = ::std::f32::NAN;
+        assert_eq!(loaded.ops_len(), 1);
+
+        // undo and redo check the NaN value bitwise too.
+        loaded.undo();
+        assert_eq!(*loaded.data.raw, 0.0);
+        loaded.redo();
+        assert!(loaded.data.raw.is_nan());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn capture_robot_op() {
+        let mut ws = new_space();
+        *ws.get_mut().x = 1.0;
+        *ws.get_mut().x = 2.0;
+        ws.undo();
+
+        *ws.data.y = 5.0;
+        let r1 = ws.capture_robot_op().unwrap();
+        assert!(ws.history().nth(1).unwrap().is_robot_op());
+        assert_eq!(ws.top_ver(), Some(&r1));
+
+        // the redo op is rebased and kept.
+        ws.redo();
+        assert_eq!(*ws.data.x, 2.0);
+        ws.undo_local().unwrap();
+        ws.undo_local().unwrap();
+        assert_eq!(*ws.data.x, 0.0);
+        assert_eq!(*ws.data.y, 5.0);
+    }
+
+    #[test]
+    fn capture_robot_op_conflict() {
+        let mut ws = new_space();
+        ws.set_merge_policy(MergePolicy::Conflict);
+        *ws.get_mut().x = 1.0;
+        *ws.get_mut().y = 2.0;
+        *ws.get_mut().x = 3.0;
+        ws.undo();
+        ws.undo();
+        ws.undo();
+
+        // the second redo op conflicts, it and the ops after it are dropped.
+        *ws.data.y = 5.0;
+        ws.capture_robot_op();
+        assert_eq!(ws.history().len(), 2);
+        ws.redo();
+        assert_eq!(*ws.data.x, 1.0);
+        assert_eq!(ws.redo(), None);
+        assert_eq!(*ws.data.x, 1.0);
+        assert_eq!(*ws.data.y, 5.0);
+    }
+
+    #[test]
+    fn shared_workspace() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SharedWorkspace<Point>>();
+
+        let shared = SharedWorkspace::new(Point! { x: 0.0, y: 0.0 });
+        shared.begin_op();
+        *shared.get_mut().x = 1.0;
+
+        let background = shared.clone();
+        let import = thread::spawn(move || {
+            // wait the batch finished.
+            *background.robot_mut().y = 7.0;
+        });
+        *shared.get_mut().x = 2.0;
+        shared.end_op();
+        import.join().unwrap();
+
+        {
+            let ws = shared.lock();
+            assert_eq!(ws.ops_len(), 1);
+            assert_eq!(ws.robot_ops_len(), 1);
+        }
+        shared.undo_local().unwrap();
+        let ws = shared.lock();
+        assert_eq!(*ws.data.x, 0.0);
+        assert_eq!(*ws.data.y, 7.0);
+    }
+
+    #[test]
+    fn undo_scope() {
+        let path = journal_path("undo_scope");
+        let mut ws = Workspace::with_journal(Point! { x: 0.0, y: 0.0 }, &path).unwrap();
+        ws.add_scope("x").unwrap();
+        // split to an op in scope `x` and an op of the rest.
+        action_modify(&mut ws, 1.0, 1.0);
+        assert_eq!(ws.history().len(), 2);
+        *ws.get_mut().y = 2.0;
+        *ws.get_mut().x = 3.0;
+
+        ws.undo_scope("x").unwrap();
+        assert_eq!(*ws.data.x, 1.0);
+        ws.undo_scope("x").unwrap();
+        assert_eq!(*ws.data.x, 0.0);
+        assert_eq!(*ws.data.y, 2.0);
+
+        // a change out of the scope keeps the scope's redo ops.
+        *ws.get_mut().y = 4.0;
+        ws.redo_scope("x").unwrap();
+        assert_eq!(*ws.data.x, 1.0);
+        ws.undo_scope("x").unwrap();
+        ws.redo_scope("x").unwrap();
+        ws.redo_scope("x").unwrap();
+        assert_eq!(*ws.data.x, 3.0);
+        assert_eq!(*ws.data.y, 4.0);
+        assert_eq!(ws.redo_scope("x"), Ok(None));
+
+        // `redo` doesn't redo the ops kept for other scopes.
+        ws.undo_scope("x").unwrap();
+        *ws.get_mut().y = 5.0;
+        assert_eq!(ws.redo(), None);
+        assert_eq!(*ws.data.x, 1.0);
+        assert_eq!(ws.redo_scope("x"), Ok(Some(0)));
+        assert_eq!(*ws.data.x, 3.0);
+
+        let recovered = Workspace::recover(Point! { x: 0.0, y: 0.0 }, &path).unwrap();
+        assert_eq!(versions(&recovered), versions(&ws));
+        assert_eq!(recovered.history().cursor(), ws.history().cursor());
+        assert_eq!(*recovered.data.x, 3.0);
+        assert_eq!(*recovered.data.y, 5.0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn scope_redo_over_dropped_robot_op() {
+        let mut ws = new_space();
+        ws.add_scope("x").unwrap();
+        *ws.get_mut().y = 1.0;
+        *ws.data.x = 5.0;
+        ws.capture_robot_op().unwrap();
+        *ws.get_mut().x = 7.0;
+        ws.undo();
+        ws.undo();
+        assert_eq!(*ws.data.x, 0.0);
+
+        // the robot op is dropped, the kept op of `x` is rebased over it.
+        *ws.get_mut().y = 2.0;
+        ws.redo_scope("x").unwrap();
+        assert_eq!(*ws.data.x, 7.0);
+        ws.undo_scope("x").unwrap();
+        assert_eq!(*ws.data.x, 0.0);
+        assert_eq!(*ws.data.y, 2.0);
+    }
+
+    #[test]
+    fn add_scope() {
+        let mut ws = new_space();
+        assert_eq!(ws.add_scope("z"), Err(ScopeError::PathNotFound));
+        assert_eq!(ws.add_scope("x.a"), Err(ScopeError::PathNotFound));
+        assert_eq!(ws.add_scope(""), Err(ScopeError::PathNotFound));
+        assert_eq!(ws.add_scope("x"), Ok(()));
+        assert_eq!(ws.add_scope("x"), Err(ScopeError::Overlap));
+    }
+
+    #[test]
+    fn json_patch() {
+        let mut ws = new_space();
+        *ws.get_mut().x = 1.0;
+        let ver = ws.top_ver().unwrap().clone();
+        let replace = |path: &str, value: f32| PatchOp::Replace {
+            path: path.to_string(),
+            value: Value::from(value),
+        };
+        assert_eq!(ws.json_patch(&ver), Some(vec![replace("/x", 1.0)]));
+
+        ws.apply_json_patch(&[replace("/y", 5.0)]).unwrap();
+        assert_eq!(*ws.data.y, 5.0);
+        assert!(ws.history().last().unwrap().is_robot_op());
+        ws.undo_local().unwrap();
+        assert_eq!(*ws.data.x, 0.0);
+        assert_eq!(*ws.data.y, 5.0);
+
+        // nothing changed if any operation failed.
+        let remove = PatchOp::Remove {
+            path: "/x".to_string(),
+        };
+        let res = ws.apply_json_patch(&[replace("/y", 6.0), remove]);
+        assert_eq!(res, Err(PatchError::Unsupported("/x".to_string())));
+        let res = ws.apply_json_patch(&[replace("/z", 6.0)]);
+        assert_eq!(res, Err(PatchError::PathNotFound("/z".to_string())));
+        assert_eq!(*ws.data.y, 5.0);
+        assert_eq!(ws.history().len(), 2);
+
+        // the changes not captured are kept as a robot op before the patch.
+        *ws.data.x = 2.0;
+        ws.apply_json_patch(&[replace("/y", 6.0)]).unwrap();
+        assert_eq!(ws.history().len(), 4);
+        assert_eq!(*ws.data.x, 2.0);
+        assert_eq!(*ws.data.y, 6.0);
+        assert!(ws.history().take(3).all(|e| e.is_robot_op()));
+    }
+
+    #[test]
+    fn describe_history() {
+        let mut ws = new_space();
+        action_modify(&mut ws, 1.0, 2.0);
+        *ws.get_mut().x = 3.0;
+        ws.undo();
+
+        let ops = versions(&ws);
+        assert_eq!(ws.history().next().unwrap().op().to_string(), "x: 0.0 → 1.0, y: 0.0 → 2.0");
+        assert_eq!(
+            ws.history().to_string(),
+            format!(
+                "0. user {}: x: 0.0 → 1.0, y: 0.0 → 2.0\n--> cursor\n1. user {}: x: 1.0 → 3.0\n",
+                ops[0].to_hex(),
+                ops[1].to_hex()
+            )
+        );
+    }
+
+    #[test]
+    fn describe_history_string() {
+        let mut ws = Workspace::new(Note! { text: "你好".to_string() });
+        ws.get_mut().text.push_str("!");
+        ws.get_mut().text.replace_range(0..3, "您");
+
+        // the offsets are bytes, `!` is after the 6 bytes of `你好`.
+        let ops = ws.history().map(|e| e.version().clone()).collect::<Vec<_>>();
+        assert_eq!(
+            ws.history().to_string(),
+            format!(
+                "0. user {}: text: @6{{+!+}}\n1. user {}: text: @0[-你-]{{+您+}}\n--> cursor\n",
+                ops[0].to_hex(),
+                ops[1].to_hex()
+            )
+        );
+    }
+
+    #[test]
+    fn to_dot() {
+        let mut ws = new_space();
+        *ws.get_mut().x = 1.0;
+        *ws.data.y = 5.0;
+        ws.capture_robot_op();
+        *ws.get_mut().x = 2.0;
+        ws.undo();
+
+        let ops = versions(&ws);
+        let dot = ws.to_dot();
+        assert!(dot.starts_with("digraph history {\n"));
+        assert!(dot.contains(&format!(
+            "op0 [shape=box, style=solid, label=\"0. user {}\\nx: 0.0 → 1.0\"];",
+            ops[0].to_hex()
+        )));
+        assert!(dot.contains("op1 [shape=ellipse, style=solid"));
+        assert!(dot.contains("op2 [shape=box, style=dashed"));
+        assert!(dot.contains("op1 -> op2 [style=dashed];"));
+        assert!(dot.contains("cursor -> op1 [style=dotted];"));
+    }
+
+    #[test]
+    fn to_dot_escape() {
+        let mut ws = Workspace::new(Note! { text: String::new() });
+        ws.get_mut().text.push_str("a\r\nb\n\"c\"");
+
+        let dot = ws.to_dot();
+        assert!(dot.contains("\\ntext: @0{+a\\nb\\n\\\"c\\\"+}\"];\n"));
+        assert_eq!(dot.lines().filter(|line| line.contains("label=")).count(), 1);
+    }
 }
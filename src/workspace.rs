@@ -1,8 +1,18 @@
+use std::fmt;
+use std::io;
 use std::ops::{Deref, DerefMut};
 use std::num::Wrapping;
+use std::path::Path;
 use bson::oid::ObjectId;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use checkpoint::Checkpoints;
+use journal::{invalid_data, Entry, Journal};
+use scope::Scopes;
 
 pub use rundo_types::*;
+use rundo_types::json_patch::{from_json_patch, to_json_patch, JsonPatch, PatchError, PatchOp};
 pub use rundo_attrs::*;
 
 #[derive(PartialEq, Debug)]
@@ -12,8 +22,8 @@ pub enum WorkSpaceOp<T> {
     UserOp((ObjectId, T)),
     /// Robot Op means, some data change occurs not in any RefGuard lifetime.
     /// In most case Robot Op come from server, or sync from other client change.
-    /// An Robot Op will not become an individual undo/redo Op, but
-    /// will be comsumed by the nearest UserOp, when do undo redo.
+    /// An Robot Op will not become an individual undo/redo Op, and `undo_local`
+    /// will keep it, only the changes of the nearest UserOp are reverted.
     RobotOp((ObjectId, T)),
 }
 
@@ -43,6 +53,47 @@ impl<T> WorkSpaceOp<T> {
     pub fn is_robot_op(&self) -> bool {
         !self.is_user_op()
     }
+
+    /// the same kind op with the same version, but replace the content by `op`.
+    pub(crate) fn with_op<U>(&self, op: U) -> WorkSpaceOp<U> {
+        match self {
+            &WorkSpaceOp::RobotOp(ref r) => WorkSpaceOp::RobotOp((r.0.clone(), op)),
+            &WorkSpaceOp::UserOp(ref u) => WorkSpaceOp::UserOp((u.0.clone(), op)),
+        }
+    }
+}
+
+/// the kind, version and changes of the op, like `user 5b...: x: 1.0 → 2.0`.
+impl<T: fmt::Display> fmt::Display for WorkSpaceOp<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = if self.is_user_op() { "user" } else { "robot" };
+        write!(f, "{} {}: {}", kind, self.version().to_hex(), self.op())
+    }
+}
+
+/// Errors may occur when undo an individual op or apply a robot op.
+#[derive(PartialEq, Debug)]
+pub enum OpError {
+    /// the version is not in the workspace history.
+    VersionNotFound,
+    /// the op is not applied to data, it can be redo but not undo.
+    NotApplied,
+    /// other ops changed the same data, and can't be merged.
+    Conflict,
+}
+
+/// Errors may occur when register an undo scope.
+#[derive(PartialEq, Debug)]
+pub enum ScopeError {
+    /// the path is not a field of data.
+    PathNotFound,
+    /// the path is inside another scope, or contains another scope.
+    Overlap,
+}
+
+/// a journal entry failed to replay, the journal is not written by the same history.
+fn op_error(err: OpError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", err))
 }
 
 /// RefGuard is an help object to auto record op
@@ -84,6 +135,128 @@ pub struct SpaceIter {
     pub(crate) curr: usize,
 }
 
+/// A read only view of one op in the workspace history.
+pub struct HistoryEntry<'a, T: 'a> {
+    idx: usize,
+    op: &'a WorkSpaceOp<T>,
+    applied: bool,
+    top: bool,
+}
+
+impl<'a, T> HistoryEntry<'a, T> {
+    /// position of this entry in the history, start from zero.
+    pub fn index(&self) -> usize {
+        self.idx
+    }
+
+    pub fn version(&self) -> &'a ObjectId {
+        self.op.version()
+    }
+
+    /// the time this op was generated, in seconds since the Unix epoch.
+    pub fn timestamp(&self) -> u32 {
+        self.op.version().timestamp()
+    }
+
+    pub fn op(&self) -> &'a T {
+        self.op.op()
+    }
+
+    pub fn is_user_op(&self) -> bool {
+        self.op.is_user_op()
+    }
+
+    pub fn is_robot_op(&self) -> bool {
+        self.op.is_robot_op()
+    }
+
+    /// if this op is before the cursor, which means it's already applied to data
+    /// and can be undo, otherwise it's a future op can be redo.
+    pub fn is_applied(&self) -> bool {
+        self.applied
+    }
+
+    /// if this op is the last applied op, just before the cursor.
+    pub fn is_top(&self) -> bool {
+        self.top
+    }
+}
+
+/// Iterator over workspace history, both past ops and future(redo) ops,
+/// from the oldest to the newest.
+pub struct History<'a, T: 'a> {
+    stack: &'a [WorkSpaceOp<T>],
+    base: usize,
+    cursor: usize,
+    front: usize,
+    back: usize,
+}
+
+impl<'a, T> History<'a, T> {
+    /// the cursor position, ops with index less than it are applied.
+    pub fn cursor(&self) -> usize {
+        self.cursor - self.base
+    }
+
+    fn entry(&self, idx: usize) -> HistoryEntry<'a, T> {
+        HistoryEntry {
+            idx: idx - self.base,
+            op: &self.stack[idx],
+            applied: idx < self.cursor,
+            top: idx + 1 == self.cursor,
+        }
+    }
+}
+
+/// A numbered list of the ops, the cursor is a line between the applied ops
+/// and the ops can be redo.
+impl<'a, T: fmt::Display> fmt::Display for History<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for idx in self.front..self.back {
+            if idx == self.cursor {
+                writeln!(f, "--> cursor")?;
+            }
+            writeln!(f, "{}. {}", idx - self.base, self.stack[idx])?;
+        }
+        if self.cursor == self.back {
+            writeln!(f, "--> cursor")?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, T> Iterator for History<'a, T> {
+    type Item = HistoryEntry<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.front < self.back {
+            let entry = self.entry(self.front);
+            self.front += 1;
+            Some(entry)
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for History<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front < self.back {
+            self.back -= 1;
+            Some(self.entry(self.back))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, T> ExactSizeIterator for History<'a, T> {}
+
 /// Workspace is the data store in rundo.
 pub struct Workspace<T: Rundo + 'static> {
     pub data: T,
@@ -92,6 +265,13 @@ pub struct Workspace<T: Rundo + 'static> {
     pub(crate) batch: i32,
     pub(crate) version: Option<ObjectId>,
     pub(crate) iter: SpaceIter,
+    pub(crate) policy: MergePolicy,
+    pub(crate) journal: Option<Journal<T::Op>>,
+    pub(crate) checkpoints: Option<Checkpoints<T>>,
+    /// history changes are not applied to data, when replay a journal
+    /// before its last snapshot.
+    pub(crate) detached: bool,
+    pub(crate) scopes: Scopes<T::Op>,
 }
 
 const STACK_DEFAULT_SIZE: usize = 128;
@@ -105,9 +285,192 @@ impl<T: Rundo> Workspace<T> {
             batch: 0,
             version: None,
             iter: SpaceIter { base: 0, curr: 0 },
+            policy: MergePolicy::LastWriterWins,
+            journal: None,
+            checkpoints: None,
+            detached: false,
+            scopes: Scopes::new(),
         };
     }
 
+    /// create a workspace which append every history change to a journal file
+    /// at `path`, so the workspace can be recovered by `Workspace::recover`
+    /// with the same `data` after a crash. An exist file will be truncated.
+    pub fn with_journal<P: AsRef<Path>>(data: T, path: P) -> io::Result<Self>
+    where
+        T::Op: Serialize,
+    {
+        let mut ws = Workspace::new(data);
+        ws.journal = Some(Journal::create(path)?);
+        Ok(ws)
+    }
+
+    /// rebuild a workspace by replay the journal at `path` on `data`, both
+    /// the data state and the undo/redo history are restored. `data` must be
+    /// the same as which the journal created with. The recovered workspace
+    /// continue to append to the journal.
+    pub fn recover<P: AsRef<Path>>(data: T, path: P) -> io::Result<Self>
+    where
+        T::Op: Serialize + DeserializeOwned + Invert + Transform,
+    {
+        let (journal, entries) = Journal::open(path)?;
+        let mut ws = Workspace::new(data);
+        ws.replay(entries)?;
+        ws.journal = Some(journal);
+        Ok(ws)
+    }
+
+    /// like `recover`, but restore data from the last snapshot in the journal,
+    /// and only the ops after it are applied to data. The ops before it are
+    /// still in history, so can be undone.
+    pub fn load<P: AsRef<Path>>(data: T, path: P) -> io::Result<Self>
+    where
+        T: DeserializeOwned,
+        T::Op: Serialize + DeserializeOwned + Invert + Transform,
+    {
+        let (journal, mut entries) = Journal::open(path)?;
+        let mut ws = Workspace::new(data);
+        let last_snapshot = entries.iter().rposition(|entry| match entry {
+            &Entry::Snapshot(_) => true,
+            _ => false,
+        });
+        if let Some(idx) = last_snapshot {
+            let tail = entries.split_off(idx + 1);
+            if let Some(Entry::Snapshot(snapshot)) = entries.pop() {
+                ws.detached = true;
+                ws.replay(entries)?;
+                ws.detached = false;
+                ws.data = bincode::deserialize(&snapshot).map_err(invalid_data)?;
+            }
+            entries = tail;
+        }
+        ws.replay(entries)?;
+        ws.journal = Some(journal);
+        Ok(ws)
+    }
+
+    fn replay(&mut self, entries: Vec<Entry<T::Op>>) -> io::Result<()>
+    where
+        T::Op: Invert + Transform,
+    {
+        self.scopes.enable_rebase();
+        for entry in entries {
+            match entry {
+                Entry::UserOp(ver, op) => {
+                    if !self.detached {
+                        self.data.forward(&op);
+                    }
+                    self.push_user_op(ObjectId::with_bytes(ver), op, None);
+                }
+                Entry::ScopedOp(ver, scope, op) => {
+                    if !self.detached {
+                        self.data.forward(&op);
+                    }
+                    self.push_user_op(ObjectId::with_bytes(ver), op, Some(scope));
+                }
+                Entry::RobotOp(base, ver, op) => {
+                    let base = base.map(ObjectId::with_bytes);
+                    self.apply_robot_op(base.as_ref(), ObjectId::with_bytes(ver), op)
+                        .map_err(op_error)?;
+                }
+                Entry::CapturedRobotOp(ver, op) => {
+                    if !self.detached {
+                        self.data.forward(&op);
+                    }
+                    self.push_robot_op(ObjectId::with_bytes(ver), op);
+                }
+                Entry::Undo => {
+                    self.undo();
+                }
+                Entry::UndoLocal => {
+                    self.undo_local().map_err(op_error)?;
+                }
+                Entry::Redo => {
+                    self.redo();
+                }
+                Entry::SkipTo(ver) => {
+                    self.skip_to(&ObjectId::with_bytes(ver));
+                }
+                Entry::Policy(policy) => self.set_merge_policy(policy),
+                Entry::Snapshot(_) => {}
+                Entry::UndoScope(scope) => {
+                    self.undo_scope(&scope).map_err(op_error)?;
+                }
+                Entry::RedoScope(scope) => {
+                    self.redo_scope(&scope).map_err(op_error)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// the error when write the journal, the journal stop working after it.
+    pub fn journal_error(&self) -> Option<&io::Error> {
+        self.journal.as_ref().and_then(|journal| journal.error())
+    }
+
+    fn log(&mut self, entry: Entry<&T::Op>) {
+        if let Some(ref mut journal) = self.journal {
+            journal.append(&entry);
+        }
+    }
+
+    /// keep a copy of data at current version, `skip_to` a far away version
+    /// will start from the nearest checkpoint. If the workspace has a journal,
+    /// the data is also written to it as a snapshot, used by `Workspace::load`.
+    pub fn checkpoint(&mut self)
+    where
+        T: Clone + Serialize,
+    {
+        assert!(self.batch == 0, "checkpoint during a batch is not allowed.");
+        if self.checkpoints.is_none() {
+            self.checkpoints = Some(Checkpoints::new());
+        }
+        self.take_checkpoint();
+    }
+
+    /// take a checkpoint every `interval` ops, `0` means never, the default.
+    pub fn set_checkpoint_interval(&mut self, interval: usize)
+    where
+        T: Clone + Serialize,
+    {
+        if self.checkpoints.is_none() {
+            self.checkpoints = Some(Checkpoints::new());
+        }
+        if let Some(ref mut checkpoints) = self.checkpoints {
+            checkpoints.interval = interval;
+        }
+    }
+
+    fn take_checkpoint(&mut self) {
+        if let Some(ref mut checkpoints) = self.checkpoints {
+            let snapshot = checkpoints.take(self.iter.curr, &self.data);
+            if let Some(ref mut journal) = self.journal {
+                journal.append_snapshot(snapshot);
+            }
+        }
+    }
+
+    /// take a checkpoint if there are `interval` ops since the last one.
+    fn auto_checkpoint(&mut self) {
+        let due = match self.checkpoints {
+            Some(ref checkpoints) if checkpoints.interval > 0 && !self.detached => {
+                let last = checkpoints.last_pos().unwrap_or(self.iter.base);
+                self.iter.curr >= last + checkpoints.interval
+            }
+            _ => false,
+        };
+        if due {
+            self.take_checkpoint();
+        }
+    }
+
+    fn drop_checkpoints_after(&mut self, pos: usize) {
+        if let Some(ref mut checkpoints) = self.checkpoints {
+            checkpoints.drop_after(pos);
+        }
+    }
+
     pub fn begin_op(&mut self) {
         if self.batch == 0 {
             let oid = ObjectId::new().expect("rundo generate version objectid failed");
@@ -125,17 +488,54 @@ impl<T: Rundo> Workspace<T> {
         if self.batch == 0 {
             if let Some(op) = self.data.change_op() {
                 self.data.reset();
-                let curr = self.iter.curr;
-                self.stack.drain(curr..);
-                {
-                    let oid = self.version.as_ref().unwrap();
-                    self.stack.push(WorkSpaceOp::UserOp((oid.clone(), op)));
-                }
-                self.version = None;
-                self.user_ops_len += 1;
-                self.iter.curr += 1;
+                let oid = self.version.take().unwrap();
+                let mut parts = self.scopes.split_op(op).into_iter();
+                if let Some((scope, op)) = parts.next() {
+                    self.push_user_op(oid, op, scope);
+                }
+                for (scope, op) in parts {
+                    let oid = ObjectId::new().expect("rundo generate version objectid failed");
+                    self.push_user_op(oid, op, scope);
+                }
+            }
+        }
+    }
+
+    /// register an undo scope by a dotted field path, like `"canvas"` or
+    /// `"panel.color"`. The changes in a scope are split from a user op as a
+    /// separate user op, which can be undone by `undo_scope` without touching
+    /// other scopes, and a new change in it only drop the redo ops of it.
+    /// The redo ops of other scopes are kept, but only `redo_scope` can redo
+    /// them, `redo` stops before them. They are rebased over the dropped ops
+    /// by the merge policy, one can't be rebased is dropped too.
+    /// Scopes can't overlap. They are not written to the journal, register
+    /// them again after `recover` or `load`.
+    pub fn add_scope(&mut self, path: &str) -> Result<(), ScopeError>
+    where
+        T::Op: SplitOp + Invert + Transform,
+    {
+        self.scopes.add(path)
+    }
+
+    fn push_user_op(&mut self, ver: ObjectId, op: T::Op, scope: Option<String>) {
+        let curr = self.iter.curr;
+        let redo_ops = self.stack.split_off(curr);
+        // the ops of other scopes changed other data, keep them to redo.
+        let kept = self.scopes
+            .keep_redo(redo_ops, scope.as_ref().map(|s| s.as_str()), self.policy);
+        self.stack.extend(kept);
+        self.drop_checkpoints_after(curr);
+        match scope {
+            Some(scope) => {
+                self.log(Entry::ScopedOp(ver.bytes(), scope.clone(), &op));
+                self.scopes.tag(&ver, scope);
             }
+            None => self.log(Entry::UserOp(ver.bytes(), &op)),
         }
+        self.stack.insert(curr, WorkSpaceOp::UserOp((ver, op)));
+        self.user_ops_len += 1;
+        self.iter.curr += 1;
+        self.auto_checkpoint();
     }
 
     pub fn get_mut(&mut self) -> RefGuard<T> {
@@ -161,12 +561,17 @@ impl<T: Rundo> Workspace<T> {
         let data = &mut self.data;
         let iter = &mut self.iter;
         let user_ops_len = &mut self.user_ops_len;
+        let detached = self.detached;
         if let Some(i) = idx {
             (0..i + 1).for_each(|i| {
                 let op = stack[i].op();
-                data.forward(&op);
+                if !detached {
+                    data.forward(&op);
+                }
                 iter.curr += 1;
-                *user_ops_len += 1;
+                if stack[i].is_user_op() {
+                    *user_ops_len += 1;
+                }
             })
         };
 
@@ -186,25 +591,377 @@ impl<T: Rundo> Workspace<T> {
         let data = &mut self.data;
         let iter = &mut self.iter;
         let user_ops_len = &mut self.user_ops_len;
+        let detached = self.detached;
         if let Some(idx) = idx {
             let idx = if boundary_open { idx + 1 } else { idx };
             (idx..stack.len()).rev().for_each(|i| {
                 let op = stack[i].op();
-                data.back(&op);
+                if !detached {
+                    data.back(&op);
+                }
                 iter.curr -= 1;
-                *user_ops_len -= 1;
+                if stack[i].is_user_op() {
+                    *user_ops_len -= 1;
+                }
             });
         };
 
         idx
     }
 
+    /// redo the next user op, the robot ops before it are redone too. The ops
+    /// of a scope kept by a change out of it can only be redone by
+    /// `redo_scope`.
     pub fn redo(&mut self) -> Option<usize> {
-        self.redo_by(|e| e.is_user_op())
+        let kept = {
+            let scopes = &self.scopes;
+            self.stack[self.iter.curr..]
+                .iter()
+                .find(|e| e.is_user_op())
+                .map_or(false, |e| scopes.is_kept(e.version()))
+        };
+        if kept {
+            return None;
+        }
+        let idx = self.redo_by(|e| e.is_user_op());
+        if idx.is_some() {
+            self.log(Entry::Redo);
+        }
+        idx
     }
 
+    /// undo the last user op, and the robot ops after it.
     pub fn undo(&mut self) -> Option<usize> {
-        self.undo_by(|e| e.is_user_op(), false)
+        let idx = self.undo_by(|e| e.is_user_op(), false);
+        if idx.is_some() {
+            self.log(Entry::Undo);
+        }
+        idx
+    }
+
+    /// undo the last user op, but keep the robot ops after it. The user op is
+    /// transformed over them and moved after them in history, if they changed
+    /// the same data, robot ops win. Return `Ok(None)` if there is no user op
+    /// to undo, and `OpError::Conflict` if the robot ops can't be kept, then
+    /// nothing changed.
+    pub fn undo_local(&mut self) -> Result<Option<usize>, OpError>
+    where
+        T::Op: Invert + Transform,
+    {
+        let idx = match self.stack[self.iter.base..self.iter.curr]
+            .iter()
+            .rposition(|e| e.is_user_op())
+        {
+            Some(idx) => idx + self.iter.base,
+            None => return Ok(None),
+        };
+        let idx = self.undo_at(idx)?;
+        self.log(Entry::UndoLocal);
+        Ok(Some(idx))
+    }
+
+    /// undo the last user op in `scope`, the later ops are kept like
+    /// `undo_local`.
+    pub fn undo_scope(&mut self, scope: &str) -> Result<Option<usize>, OpError>
+    where
+        T::Op: Invert + Transform,
+    {
+        let idx = {
+            let scopes = &self.scopes;
+            self.stack[self.iter.base..self.iter.curr]
+                .iter()
+                .rposition(|e| e.is_user_op() && scopes.scope_of(e.version()) == Some(scope))
+        };
+        let idx = match idx {
+            Some(idx) => idx + self.iter.base,
+            None => return Ok(None),
+        };
+        let idx = self.undo_at(idx)?;
+        self.log(Entry::UndoScope(scope.to_string()));
+        Ok(Some(idx))
+    }
+
+    /// undo the user op at `idx`, and move it after the later ops.
+    fn undo_at(&mut self, idx: usize) -> Result<usize, OpError>
+    where
+        T::Op: Invert + Transform,
+    {
+        let curr = self.iter.curr;
+        if idx + 1 == curr {
+            self.undo_by(|e| e.is_user_op(), false);
+            return Ok(idx);
+        }
+
+        let mut inverse = self.stack[idx].op().invert();
+        let mut robots = Vec::with_capacity(curr - idx - 1);
+        for robot in &self.stack[idx + 1..curr] {
+            let rebased = robot
+                .op()
+                .transform_by(&inverse, MergePolicy::LastWriterWins)
+                .ok_or(OpError::Conflict)?;
+            inverse = inverse
+                .transform_by(robot.op(), MergePolicy::FirstWriterWins)
+                .ok_or(OpError::Conflict)?;
+            robots.push(robot.with_op(rebased));
+        }
+
+        if !self.detached {
+            self.data.forward(&inverse);
+        }
+        self.drop_checkpoints_after(idx);
+        let user = self.stack[idx].with_op(inverse.invert());
+        let redo_ops = self.stack.split_off(curr);
+        self.stack.truncate(idx);
+        self.stack.extend(robots);
+        self.stack.push(user);
+        self.stack.extend(redo_ops);
+        self.iter.curr -= 1;
+        self.user_ops_len -= 1;
+        Ok(idx)
+    }
+
+    /// redo the next user op in `scope`, the ops can be redo before it are
+    /// rebased to be after it. Return `Ok(None)` if there is no op to redo,
+    /// and `OpError::Conflict` if it can't be moved over them.
+    pub fn redo_scope(&mut self, scope: &str) -> Result<Option<usize>, OpError>
+    where
+        T::Op: Invert + Transform,
+    {
+        let curr = self.iter.curr;
+        let idx = {
+            let scopes = &self.scopes;
+            self.stack[curr..]
+                .iter()
+                .position(|e| e.is_user_op() && scopes.scope_of(e.version()) == Some(scope))
+        };
+        let idx = match idx {
+            Some(idx) => idx + curr,
+            None => return Ok(None),
+        };
+
+        let mut redo: Option<T::Op> = None;
+        let mut others = Vec::with_capacity(idx - curr);
+        for other in self.stack[curr..idx].iter().rev() {
+            let next = {
+                let op = redo.as_ref().unwrap_or(self.stack[idx].op());
+                let moved = op.transform_by(&other.op().invert(), MergePolicy::LastWriterWins)
+                    .ok_or(OpError::Conflict)?;
+                let rebased = other
+                    .op()
+                    .transform_by(&moved, MergePolicy::FirstWriterWins)
+                    .ok_or(OpError::Conflict)?;
+                others.push(other.with_op(rebased));
+                moved
+            };
+            redo = Some(next);
+        }
+
+        if !self.detached {
+            self.data.forward(redo.as_ref().unwrap_or(self.stack[idx].op()));
+        }
+        self.drop_checkpoints_after(curr);
+        let redo = redo.map(|op| self.stack[idx].with_op(op));
+        let mut later = self.stack.split_off(curr);
+        let rest = later.split_off(idx - curr + 1);
+        let op = later.pop().unwrap();
+        self.scopes.unkeep(op.version());
+        self.stack.push(redo.unwrap_or(op));
+        self.stack.extend(others.into_iter().rev());
+        self.stack.extend(rest);
+        self.iter.curr += 1;
+        self.user_ops_len += 1;
+        self.log(Entry::RedoScope(scope.to_string()));
+        Ok(Some(idx - curr))
+    }
+
+    /// undo the changes of an individual op but keep all the later ops,
+    /// the revert is recorded as a new user op, and its version returned.
+    /// The op's inverse is transformed over the later ops, if any of them
+    /// changed the same data, `OpError::Conflict` returned and nothing changed.
+    pub fn undo_op(&mut self, ver: &ObjectId) -> Result<ObjectId, OpError>
+    where
+        T::Op: Invert + Transform,
+    {
+        assert!(self.batch == 0, "undo an op during a batch is not allowed.");
+        let pos = self.position(ver).ok_or(OpError::VersionNotFound)?;
+        let curr = self.iter.curr;
+        if pos >= curr {
+            return Err(OpError::NotApplied);
+        }
+
+        let mut op = self.stack[pos].op().invert();
+        for later in &self.stack[pos + 1..curr] {
+            op = op.transform(later.op()).ok_or(OpError::Conflict)?;
+        }
+
+        self.data.forward(&op);
+        let oid = ObjectId::new().expect("rundo generate version objectid failed");
+        let scope = self.scopes.scope_of(ver).map(String::from);
+        self.push_user_op(oid.clone(), op, scope);
+        Ok(oid)
+    }
+
+    /// set how to merge robot ops with local ops when they changed the same
+    /// value, default is `MergePolicy::LastWriterWins` which means local ops win.
+    pub fn set_merge_policy(&mut self, policy: MergePolicy) {
+        self.policy = policy;
+        self.log(Entry::Policy(policy));
+    }
+
+    /// apply an op come from other place, like server or other client, as a
+    /// robot op with version `ver`. `base` is the version the op generated
+    /// on, `None` means the beginning of history.
+    /// The robot op is placed just after `base` in history, and all the ops
+    /// after `base` are rebased over it, so they can still be undo and redo.
+    pub fn apply_robot_op(
+        &mut self,
+        base: Option<&ObjectId>,
+        ver: ObjectId,
+        op: T::Op,
+    ) -> Result<(), OpError>
+    where
+        T::Op: Transform,
+    {
+        assert!(self.batch == 0, "apply robot op during a batch is not allowed.");
+        let start = match base {
+            Some(base) => self.position(base).ok_or(OpError::VersionNotFound)? + 1,
+            None => self.iter.base,
+        };
+        let curr = self.iter.curr;
+        if start > curr {
+            return Err(OpError::NotApplied);
+        }
+
+        // transform all later ops before touch data, so nothing changed if conflict.
+        // `robot` is the op transformed over the later ops one by one, and `top`
+        // is the one transformed over all applied ops, which can apply to data.
+        let policy = self.policy;
+        let mut robot: Option<T::Op> = None;
+        let mut top: Option<T::Op> = None;
+        let mut rebased = Vec::with_capacity(self.stack.len() - start);
+        for (i, later) in self.stack.iter().enumerate().skip(start) {
+            let next = {
+                let transformed = robot.as_ref().unwrap_or(&op);
+                let local = later
+                    .op()
+                    .transform_by(transformed, policy)
+                    .ok_or(OpError::Conflict)?;
+                rebased.push(later.with_op(local));
+                transformed
+                    .transform_by(later.op(), policy.reverse())
+                    .ok_or(OpError::Conflict)?
+            };
+            if i == curr {
+                top = robot.take();
+            }
+            robot = Some(next);
+        }
+        if curr == self.stack.len() {
+            top = robot;
+        }
+
+        if !self.detached {
+            self.data.forward(top.as_ref().unwrap_or(&op));
+        }
+        self.drop_checkpoints_after(start);
+        self.log(Entry::RobotOp(base.map(|base| base.bytes()), ver.bytes(), &op));
+        self.stack.truncate(start);
+        self.stack.push(WorkSpaceOp::RobotOp((ver, op)));
+        self.stack.extend(rebased);
+        self.iter.curr += 1;
+        self.auto_checkpoint();
+        Ok(())
+    }
+
+    /// record the changes of data not captured by any user op as a robot op,
+    /// like the changes made by a background task, so `undo_local` will keep
+    /// them.
+    /// The ops can be redo are rebased over it, the first one conflicts with
+    /// it and all the redo ops after that are dropped. Return the version of
+    /// the robot op.
+    pub fn capture_robot_op(&mut self) -> Option<ObjectId>
+    where
+        T::Op: Transform,
+    {
+        assert!(self.batch == 0, "capture robot op during a batch is not allowed.");
+        let op = self.data.change_op()?;
+        self.data.reset();
+        let ver = ObjectId::new().expect("rundo generate version objectid failed");
+        self.log(Entry::CapturedRobotOp(ver.bytes(), &op));
+        self.push_robot_op(ver.clone(), op);
+        Some(ver)
+    }
+
+    /// apply a JSON Patch come from other place as a robot op at the cursor,
+    /// like `capture_robot_op`. Return the version of the robot op, `None` if
+    /// nothing changed. If any operation failed, nothing changed. The changes
+    /// of data not captured yet are captured as a robot op before it.
+    /// To apply a patch generated on an old version, parse it by
+    /// `from_json_patch` and apply it by `apply_robot_op`.
+    pub fn apply_json_patch(&mut self, patch: &[PatchOp]) -> Result<Option<ObjectId>, PatchError>
+    where
+        T: JsonPatch,
+        T::Op: Transform,
+    {
+        assert!(self.batch == 0, "apply json patch during a batch is not allowed.");
+        self.capture_robot_op();
+        let op = match from_json_patch(&mut self.data, patch)? {
+            Some(op) => op,
+            None => return Ok(None),
+        };
+        self.data.forward(&op);
+        let ver = ObjectId::new().expect("rundo generate version objectid failed");
+        self.log(Entry::CapturedRobotOp(ver.bytes(), &op));
+        self.push_robot_op(ver.clone(), op);
+        Ok(Some(ver))
+    }
+
+    /// the JSON Patch of the op at version `ver`, its paths point into the
+    /// serde representation of data. Return `None` if `ver` not in history.
+    pub fn json_patch(&self, ver: &ObjectId) -> Option<Vec<PatchOp>>
+    where
+        T: JsonPatch + Clone,
+    {
+        let pos = self.position(ver)?;
+        let data = self.snapshot_at(ver)?;
+        Some(to_json_patch(&data, self.stack[pos].op()))
+    }
+
+    /// push a robot op which already applied to data at the cursor. The redo
+    /// ops are rebased over it, from the first conflicting one the rest of the
+    /// redo branch is discarded, the later ops are based on the dropped one.
+    fn push_robot_op(&mut self, ver: ObjectId, op: T::Op)
+    where
+        T::Op: Transform,
+    {
+        let curr = self.iter.curr;
+        let policy = self.policy;
+        let mut rebased = Vec::with_capacity(self.stack.len() - curr);
+        {
+            let mut robot: Option<T::Op> = None;
+            for later in &self.stack[curr..] {
+                let next = {
+                    let transformed = robot.as_ref().unwrap_or(&op);
+                    let local = later.op().transform_by(transformed, policy);
+                    let next = transformed.transform_by(later.op(), policy.reverse());
+                    match (local, next) {
+                        (Some(local), Some(next)) => {
+                            rebased.push(later.with_op(local));
+                            next
+                        }
+                        _ => break,
+                    }
+                };
+                robot = Some(next);
+            }
+        }
+
+        self.drop_checkpoints_after(curr);
+        self.stack.truncate(curr);
+        self.stack.push(WorkSpaceOp::RobotOp((ver, op)));
+        self.stack.extend(rebased);
+        self.iter.curr += 1;
+        self.auto_checkpoint();
     }
 
     /// forward to the special version, if `ver` is not front of
@@ -212,7 +969,11 @@ impl<T: Rundo> Workspace<T> {
     /// when you cann't detect the version back or front current version
     /// use `skip_to`
     pub fn redo_to(&mut self, ver: &ObjectId) -> Option<usize> {
-        self.redo_by(|op| op.version() == ver)
+        let idx = self.redo_by(|op| op.version() == ver);
+        if idx.is_some() {
+            self.log(Entry::SkipTo(ver.bytes()));
+        }
+        idx
     }
 
     /// back to a special version, if `ver` is not back of
@@ -220,12 +981,171 @@ impl<T: Rundo> Workspace<T> {
     /// when you cann't detect the version back or front current version
     /// use `skip_to`
     pub fn undo_to(&mut self, ver: &ObjectId) -> Option<usize> {
-        self.undo_by(|op| op.version() == ver, true)
+        let idx = self.undo_by(|op| op.version() == ver, true);
+        if idx.is_some() {
+            self.log(Entry::SkipTo(ver.bytes()));
+        }
+        idx
     }
 
-    /// skip to an arbitary version
+    /// skip to an arbitary version, if there is a checkpoint nearer to `ver`
+    /// than current version, start from it.
     pub fn skip_to(&mut self, ver: &ObjectId) -> Option<usize> {
-        self.undo_to(ver).or_else(|| self.redo_to(ver))
+        self.skip_from_checkpoint(ver)
+            .or_else(|| self.undo_to(ver))
+            .or_else(|| self.redo_to(ver))
+    }
+
+    fn skip_from_checkpoint(&mut self, ver: &ObjectId) -> Option<usize> {
+        let pos = self.position(ver)?;
+        let (target, curr) = (pos + 1, self.iter.curr);
+        let (from, data) = {
+            let checkpoints = self.checkpoints.as_ref()?;
+            let (from, data) = checkpoints.nearest(target)?;
+            let distance = |p: usize| if p > target { p - target } else { target - p };
+            if distance(from) >= distance(curr) {
+                return None;
+            }
+            (from, checkpoints.copy(data))
+        };
+
+        self.data = data;
+        if from < target {
+            for op in &self.stack[from..target] {
+                self.data.forward(op.op());
+            }
+        } else {
+            for op in self.stack[target..from].iter().rev() {
+                self.data.back(op.op());
+            }
+        }
+        self.user_ops_len = self.stack[self.iter.base..target]
+            .iter()
+            .filter(|e| e.is_user_op())
+            .count();
+        self.iter.curr = target;
+        self.log(Entry::SkipTo(ver.bytes()));
+        Some(if target <= curr { pos } else { pos - curr })
+    }
+
+    /// build a copy of data at the special version, the workspace cursor
+    /// and data will not be changed. Return `None` if `ver` not in history.
+    pub fn snapshot_at(&self, ver: &ObjectId) -> Option<T>
+    where
+        T: Clone,
+    {
+        let pos = self.position(ver)?;
+        let mut data = self.data.clone();
+        // drop the changes not captured by an op yet.
+        if let Some(op) = data.change_op() {
+            data.back(&op);
+        }
+        data.reset();
+
+        let curr = self.iter.curr;
+        if pos < curr {
+            self.stack[pos + 1..curr]
+                .iter()
+                .rev()
+                .for_each(|op| data.back(op.op()));
+        } else {
+            self.stack[curr..pos + 1]
+                .iter()
+                .for_each(|op| data.forward(op.op()));
+        }
+        Some(data)
+    }
+
+    /// compose the ops between two versions to one op, which change the data
+    /// at version `from` to the data at version `to`. If `from` is after `to`,
+    /// the ops are inverted.
+    /// Return `None` if any version not in history, or `from` is same as `to`.
+    pub fn diff(&self, from: &ObjectId, to: &ObjectId) -> Option<T::Op>
+    where
+        T::Op: Compose + Invert + Clone,
+    {
+        let from = self.position(from)?;
+        let to = self.position(to)?;
+        if from < to {
+            let mut ops = self.stack[from + 1..to + 1].iter().map(|op| op.op().clone());
+            let first = ops.next();
+            first.map(|first| ops.fold(first, |acc, op| acc.compose(&op)))
+        } else {
+            let mut ops = self.stack[to + 1..from + 1]
+                .iter()
+                .rev()
+                .map(|op| op.op().invert());
+            let first = ops.next();
+            first.map(|first| ops.fold(first, |acc, op| acc.compose(&op)))
+        }
+    }
+
+    fn position(&self, ver: &ObjectId) -> Option<usize> {
+        self.stack[self.iter.base..]
+            .iter()
+            .position(|op| op.version() == ver)
+            .map(|idx| idx + self.iter.base)
+    }
+
+    /// iterate all ops in history, include the ops can be redo.
+    pub fn history(&self) -> History<T::Op> {
+        History {
+            stack: &self.stack,
+            base: self.iter.base,
+            cursor: self.iter.curr,
+            front: self.iter.base,
+            back: self.stack.len(),
+        }
+    }
+
+    /// the history as a Graphviz DOT graph for debugging. User ops are boxes
+    /// and robot ops are ellipses, labeled by their index, version and
+    /// changes. The history is linear, the ops can be redo are dashed, and
+    /// branch from the cursor.
+    pub fn to_dot(&self) -> String
+    where
+        T::Op: fmt::Display,
+    {
+        let escape = |s: String| {
+            s.replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace("\r\n", "\\n")
+                .replace(|c: char| c == '\r' || c == '\n', "\\n")
+        };
+        let mut dot = String::from("digraph history {\n    rankdir=LR;\n");
+        dot.push_str("    start [shape=point];\n");
+        let history = self.history();
+        let cursor = history.cursor();
+        for entry in history {
+            let (kind, shape) = if entry.is_user_op() {
+                ("user", "box")
+            } else {
+                ("robot", "ellipse")
+            };
+            let style = if entry.is_applied() { "solid" } else { "dashed" };
+            dot.push_str(&format!(
+                "    op{} [shape={}, style={}, label=\"{}. {} {}\\n{}\"];\n",
+                entry.index(),
+                shape,
+                style,
+                entry.index(),
+                kind,
+                entry.version().to_hex(),
+                escape(entry.op().to_string()),
+            ));
+            let prev = match entry.index() {
+                0 => "start".to_string(),
+                idx => format!("op{}", idx - 1),
+            };
+            dot.push_str(&format!("    {} -> op{} [style={}];\n", prev, entry.index(), style));
+        }
+        let top = match cursor {
+            0 => "start".to_string(),
+            idx => format!("op{}", idx - 1),
+        };
+        dot.push_str("    cursor [shape=plaintext];\n");
+        dot.push_str(&format!("    cursor -> {} [style=dotted];\n}}\n", top));
+        dot
     }
 
     pub fn zip() {
@@ -237,8 +1157,10 @@ impl<T: Rundo> Workspace<T> {
     }
 
     pub fn robot_ops_len(&self) -> usize {
-        let stack_len = self.stack.len() - self.iter.base;
-        stack_len - self.ops_len()
+        self.stack[self.iter.base..self.iter.curr]
+            .iter()
+            .filter(|e| e.is_robot_op())
+            .count()
     }
 
     pub fn next_ver(&self) -> Option<&ObjectId> {
@@ -0,0 +1,391 @@
+use std::ops::{Bound, Deref, DerefMut, RangeBounds};
+
+use seg::{compose_segs, push_seg, segs_overlap, transform_segs, Seg};
+use field_path::FieldPath;
+use super::{ChangedPaths, Compose, Invert, MergePolicy, Rundo, Transform};
+
+/// equal bytes fewer than this between two changed runs are patched together,
+/// since every patch has its own cost.
+const MIN_GAP: usize = 8;
+
+/// A splice patch, the `removed` bytes at `offset` of the origin buffer are
+/// replaced by the `inserted` bytes.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct BytePatch {
+    pub offset: usize,
+    pub removed: Vec<u8>,
+    pub inserted: Vec<u8>,
+}
+
+/// An undoable byte buffer, its op is the sorted patches of the changed
+/// ranges, not a backup of the whole buffer. `replace_range`,
+/// `extend_from_slice` and `truncate` record patches directly, changes through
+/// `DerefMut` are diffed.
+#[derive(Clone)]
+pub struct BytesType {
+    pub(crate) value: Vec<u8>,
+    pub(crate) origin: Option<Vec<u8>>,
+    pub(crate) edits: Option<Vec<BytePatch>>,
+}
+
+/// impl Deref let BytesType transparent to user access the buffer.
+impl Deref for BytesType {
+    type Target = Vec<u8>;
+    fn deref(&self) -> &Vec<u8> {
+        &self.value
+    }
+}
+
+/// when user try to get a mut refercence, backup the buffer to diff later.
+impl DerefMut for BytesType {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        if self.origin.is_none() {
+            self.origin = Some(self.value.clone());
+        }
+        &mut self.value
+    }
+}
+
+impl From<Vec<u8>> for BytesType {
+    fn from(from: Vec<u8>) -> Self {
+        BytesType {
+            value: from,
+            origin: None,
+            edits: None,
+        }
+    }
+}
+
+impl BytesType {
+    /// Replaces the range with `bytes`.
+    pub fn replace_range<R>(&mut self, range: R, bytes: &[u8])
+    where
+        R: RangeBounds<usize>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n + 1,
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => self.value.len(),
+        };
+        let removed = self.value
+            .splice(start..end, bytes.iter().cloned())
+            .collect::<Vec<_>>();
+        if !removed.is_empty() || !bytes.is_empty() {
+            self.record(BytePatch {
+                offset: start,
+                removed,
+                inserted: bytes.to_vec(),
+            });
+        }
+    }
+
+    /// Appends `bytes`, like `Vec::extend_from_slice`.
+    pub fn extend_from_slice(&mut self, bytes: &[u8]) {
+        let len = self.value.len();
+        self.replace_range(len.., bytes);
+    }
+
+    /// Shortens the buffer to `len`, like `Vec::truncate`.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.value.len() {
+            self.replace_range(len.., &[]);
+        }
+    }
+
+    fn record(&mut self, patch: BytePatch) {
+        // the origin value will be diffed, include this edit.
+        if self.origin.is_some() {
+            return;
+        }
+        let patch = vec![patch];
+        let edits = match self.edits.take() {
+            Some(edits) => edits.compose(&patch),
+            None => patch,
+        };
+        // drop the patches cancelled by later edits.
+        let edits = edits
+            .into_iter()
+            .filter(|patch| patch.removed != patch.inserted)
+            .collect::<Vec<_>>();
+        if !edits.is_empty() {
+            self.edits = Some(edits);
+        }
+    }
+
+    /// the recorded edits composed with the diff of the backup, `None` if
+    /// they cancel each other.
+    fn pending_op(&self) -> Option<Vec<BytePatch>> {
+        let diffed = self.origin
+            .as_ref()
+            .map(|ori| diff_patches(ori, &self.value));
+        match self.edits.compose(&diffed) {
+            Some(ref patches) if patches.is_empty() => None,
+            patches => patches,
+        }
+    }
+}
+
+/// diff two buffers, skip the same head and tail. If the rest have the same
+/// length, only the changed runs are patched, else replace the rest.
+fn diff_patches(ori: &[u8], curr: &[u8]) -> Vec<BytePatch> {
+    let head = ori.iter().zip(curr.iter()).take_while(|&(a, b)| a == b).count();
+    let tail = ori[head..]
+        .iter()
+        .rev()
+        .zip(curr[head..].iter().rev())
+        .take_while(|&(a, b)| a == b)
+        .count();
+    let ori_rest = &ori[head..ori.len() - tail];
+    let curr_rest = &curr[head..curr.len() - tail];
+    if ori_rest.is_empty() && curr_rest.is_empty() {
+        return vec![];
+    }
+    if ori_rest.len() != curr_rest.len() {
+        return vec![BytePatch {
+            offset: head,
+            removed: ori_rest.to_vec(),
+            inserted: curr_rest.to_vec(),
+        }];
+    }
+
+    let mut patches: Vec<BytePatch> = vec![];
+    let mut run: Option<(usize, usize)> = None;
+    let flush = |start: usize, end: usize, patches: &mut Vec<BytePatch>| {
+        patches.push(BytePatch {
+            offset: head + start,
+            removed: ori_rest[start..end].to_vec(),
+            inserted: curr_rest[start..end].to_vec(),
+        })
+    };
+    for (i, (a, b)) in ori_rest.iter().zip(curr_rest.iter()).enumerate() {
+        if a == b {
+            continue;
+        }
+        run = match run {
+            Some((start, end)) if i - end < MIN_GAP => Some((start, i + 1)),
+            Some((start, end)) => {
+                flush(start, end, &mut patches);
+                Some((i, i + 1))
+            }
+            None => Some((i, i + 1)),
+        };
+    }
+    if let Some((start, end)) = run {
+        flush(start, end, &mut patches);
+    }
+    patches
+}
+
+fn to_segs(patches: &[BytePatch]) -> Vec<Seg<Vec<u8>>> {
+    let mut segs = Vec::with_capacity(patches.len() * 3);
+    let mut base = 0;
+    for patch in patches {
+        push_seg(&mut segs, Seg::Retain(patch.offset - base));
+        push_seg(&mut segs, Seg::Del(patch.removed.clone()));
+        push_seg(&mut segs, Seg::Ins(patch.inserted.clone()));
+        base = patch.offset + patch.removed.len();
+    }
+    segs
+}
+
+fn from_segs(segs: Vec<Seg<Vec<u8>>>) -> Vec<BytePatch> {
+    let mut patches: Vec<BytePatch> = Vec::with_capacity(segs.len());
+    let mut base = 0;
+    for seg in segs {
+        // a delete seg always placed before its adjacent insert seg.
+        let merge = match patches.last() {
+            Some(last) => last.offset + last.removed.len() == base,
+            None => false,
+        };
+        match seg {
+            Seg::Retain(n) => base += n,
+            Seg::Ins(inserted) => {
+                if merge && patches.last().map_or(false, |last| last.inserted.is_empty()) {
+                    patches.last_mut().unwrap().inserted = inserted;
+                } else {
+                    patches.push(BytePatch {
+                        offset: base,
+                        removed: vec![],
+                        inserted,
+                    });
+                }
+            }
+            Seg::Del(removed) => {
+                base += removed.len();
+                patches.push(BytePatch {
+                    offset: base - removed.len(),
+                    removed,
+                    inserted: vec![],
+                });
+            }
+        }
+    }
+    patches
+}
+
+impl Compose for Vec<BytePatch> {
+    fn compose(&self, next: &Self) -> Self {
+        from_segs(compose_segs(to_segs(self), to_segs(next)))
+    }
+}
+
+impl Invert for Vec<BytePatch> {
+    fn invert(&self) -> Self {
+        let mut shift = 0isize;
+        self.iter()
+            .map(|patch| {
+                let offset = (patch.offset as isize + shift) as usize;
+                shift += patch.inserted.len() as isize - patch.removed.len() as isize;
+                BytePatch {
+                    offset,
+                    removed: patch.inserted.clone(),
+                    inserted: patch.removed.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Concurrent patches are merged like string ops, unless they edit the same
+/// place and the policy is `Conflict`.
+impl Transform for Vec<BytePatch> {
+    fn transform_by(&self, other: &Self, policy: MergePolicy) -> Option<Self> {
+        let (segs, other) = (to_segs(self), to_segs(other));
+        if policy == MergePolicy::Conflict && segs_overlap(&segs, &other) {
+            return None;
+        }
+        Some(from_segs(transform_segs(segs, other)))
+    }
+}
+
+impl ChangedPaths for Vec<BytePatch> {
+    fn changed_paths(&self) -> Vec<FieldPath> {
+        vec![FieldPath::root()]
+    }
+}
+
+impl Rundo for BytesType {
+    type Op = Vec<BytePatch>;
+
+    fn dirty(&self) -> bool {
+        self.pending_op().is_some()
+    }
+
+    fn reset(&mut self) {
+        self.origin = None;
+        self.edits = None;
+    }
+
+    fn change_op(&mut self) -> Option<Self::Op> {
+        self.pending_op()
+    }
+
+    fn back(&mut self, op: &Self::Op) {
+        self.forward(&op.invert());
+    }
+
+    fn forward(&mut self, op: &Self::Op) {
+        self.reset();
+        let size = op.iter().fold(self.value.len() as isize, |acc, patch| {
+            acc + patch.inserted.len() as isize - patch.removed.len() as isize
+        });
+        let mut buf = Vec::with_capacity(size as usize);
+        let mut base = 0;
+        for patch in op {
+            let end = patch.offset + patch.removed.len();
+            debug_assert_eq!(&self.value[patch.offset..end], &patch.removed[..]);
+            buf.extend_from_slice(&self.value[base..patch.offset]);
+            buf.extend_from_slice(&patch.inserted);
+            base = end;
+        }
+        buf.extend_from_slice(&self.value[base..]);
+        self.value = buf;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_diff() {
+        let ori = (0..64).collect::<Vec<u8>>();
+        let mut bytes = BytesType::from(ori.clone());
+        bytes[3] = 0xff;
+        bytes[5] = 0xff;
+        bytes[40] = 0xff;
+        let op = bytes.change_op().unwrap();
+        assert_eq!(
+            op,
+            vec![
+                BytePatch {
+                    offset: 3,
+                    removed: vec![3, 4, 5],
+                    inserted: vec![0xff, 4, 0xff],
+                },
+                BytePatch {
+                    offset: 40,
+                    removed: vec![40],
+                    inserted: vec![0xff],
+                },
+            ]
+        );
+
+        bytes.back(&op);
+        assert_eq!(*bytes, ori);
+        bytes.forward(&op);
+        assert_eq!(bytes[40], 0xff);
+    }
+
+    #[test]
+    fn bytes_edit() {
+        let mut bytes = BytesType::from(vec![1, 2, 3, 4]);
+        bytes.replace_range(1..3, &[7]);
+        bytes.extend_from_slice(&[8, 9]);
+        bytes.truncate(4);
+        assert_eq!(*bytes, vec![1, 7, 4, 8]);
+        let op = bytes.change_op().unwrap();
+        assert_eq!(
+            op,
+            vec![
+                BytePatch {
+                    offset: 1,
+                    removed: vec![2, 3],
+                    inserted: vec![7],
+                },
+                BytePatch {
+                    offset: 4,
+                    removed: vec![],
+                    inserted: vec![8],
+                },
+            ]
+        );
+
+        let mut other = BytesType::from(vec![1, 2, 3, 4]);
+        other.insert(0, 0);
+        let other_op = other.change_op().unwrap();
+        bytes.back(&op);
+        bytes.forward(&other_op);
+        bytes.forward(&op.transform(&other_op).unwrap());
+        assert_eq!(*bytes, vec![0, 1, 7, 4, 8]);
+    }
+
+    #[test]
+    fn bytes_cancelled_edit() {
+        let mut bytes = BytesType::from(vec![1, 2, 3]);
+        bytes.extend_from_slice(&[4, 5]);
+        bytes.truncate(3);
+        bytes.replace_range(1..2, &[2]);
+        assert!(!bytes.dirty());
+        assert_eq!(bytes.change_op(), None);
+
+        bytes[0] = 1;
+        assert!(!bytes.dirty());
+        assert_eq!(bytes.change_op(), None);
+    }
+}
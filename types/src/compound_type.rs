@@ -0,0 +1,281 @@
+use std::fmt::Debug;
+
+use primitive_type::{ValueType, VtOp};
+use field_path::FieldPath;
+use super::{ChangedPaths, Compose, Invert, MergePolicy, Rundo, Transform};
+
+/// the op of an element, `None` if not changed.
+fn elem_op<T>(prev: &T, curr: &T) -> Option<VtOp<T>>
+where
+    T: Clone + PartialEq,
+{
+    if prev == curr {
+        None
+    } else {
+        Some(VtOp {
+            prev: prev.clone(),
+            curr: curr.clone(),
+        })
+    }
+}
+
+/// the ops of changed elements with their indexes.
+fn elems_op<T>(prev: &[T], curr: &[T]) -> Option<Vec<(usize, VtOp<T>)>>
+where
+    T: Clone + PartialEq,
+{
+    let ops = prev.iter()
+        .zip(curr.iter())
+        .enumerate()
+        .filter_map(|(idx, (prev, curr))| elem_op(prev, curr).map(|op| (idx, op)))
+        .collect::<Vec<_>>();
+    if ops.is_empty() {
+        None
+    } else {
+        Some(ops)
+    }
+}
+
+/// An array op only carries the changed elements, sorted by index.
+impl<T> Compose for Vec<(usize, VtOp<T>)>
+where
+    T: Clone,
+{
+    fn compose(&self, next: &Self) -> Self {
+        let mut ops = self.clone();
+        for &(idx, ref op) in next {
+            match ops.binary_search_by_key(&idx, |&(i, _)| i) {
+                Ok(pos) => ops[pos].1 = ops[pos].1.compose(op),
+                Err(pos) => ops.insert(pos, (idx, op.clone())),
+            }
+        }
+        ops
+    }
+}
+
+impl<T> Invert for Vec<(usize, VtOp<T>)>
+where
+    T: Clone,
+{
+    fn invert(&self) -> Self {
+        self.iter().map(|&(idx, ref op)| (idx, op.invert())).collect()
+    }
+}
+
+impl<T> Transform for Vec<(usize, VtOp<T>)>
+where
+    T: Clone + PartialEq,
+{
+    fn transform_by(&self, other: &Self, policy: MergePolicy) -> Option<Self> {
+        let mut ops = Vec::with_capacity(self.len());
+        for &(idx, ref op) in self {
+            match other.iter().find(|&&(i, _)| i == idx) {
+                Some(&(_, ref other)) => ops.push((idx, op.transform_by(other, policy)?)),
+                None => ops.push((idx, op.clone())),
+            }
+        }
+        Some(ops)
+    }
+}
+
+impl<T> ChangedPaths for Vec<(usize, VtOp<T>)> {
+    fn changed_paths(&self) -> Vec<FieldPath> {
+        self.iter().map(|&(idx, _)| FieldPath::index(idx)).collect()
+    }
+}
+
+macro_rules! array_rundo {
+    ($($n: expr),*) => {$(
+        impl<T> Rundo for ValueType<[T; $n]>
+        where
+            T: Clone + PartialEq + Debug,
+        {
+            type Op = Vec<(usize, VtOp<T>)>;
+
+            fn dirty(&self) -> bool {
+                match self.origin {
+                    Some(ref ori) => *ori != self.value,
+                    None => false,
+                }
+            }
+
+            fn reset(&mut self) {
+                self.origin = None;
+            }
+
+            fn change_op(&mut self) -> Option<Self::Op> {
+                match self.origin {
+                    Some(ref ori) if *ori != self.value => elems_op(ori, &self.value),
+                    _ => None,
+                }
+            }
+
+            fn back(&mut self, op: &Self::Op) {
+                for &(idx, ref op) in op {
+                    debug_assert_eq!(self.value[idx], op.curr);
+                    self.value[idx] = op.prev.clone();
+                }
+                self.reset();
+            }
+
+            fn forward(&mut self, op: &Self::Op) {
+                for &(idx, ref op) in op {
+                    debug_assert_eq!(op.prev, self.value[idx]);
+                    self.value[idx] = op.curr.clone();
+                }
+                self.reset();
+            }
+        }
+    )*};
+}
+
+array_rundo!(
+    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25,
+    26, 27, 28, 29, 30, 31, 32
+);
+
+/// A tuple op is a tuple of element ops, `None` for the elements not changed.
+macro_rules! tuple_rundo {
+    ($(($($ty: ident $idx: tt),+))*) => {$(
+        impl<$($ty),+> Compose for ($(Option<VtOp<$ty>>,)+)
+        where
+            $($ty: Clone),+
+        {
+            fn compose(&self, next: &Self) -> Self {
+                ($(self.$idx.compose(&next.$idx),)+)
+            }
+        }
+
+        impl<$($ty),+> Invert for ($(Option<VtOp<$ty>>,)+)
+        where
+            $($ty: Clone),+
+        {
+            fn invert(&self) -> Self {
+                ($(self.$idx.invert(),)+)
+            }
+        }
+
+        impl<$($ty),+> Transform for ($(Option<VtOp<$ty>>,)+)
+        where
+            $($ty: Clone + PartialEq),+
+        {
+            fn transform_by(&self, other: &Self, policy: MergePolicy) -> Option<Self> {
+                Some(($(self.$idx.transform_by(&other.$idx, policy)?,)+))
+            }
+        }
+
+        impl<$($ty),+> ChangedPaths for ($(Option<VtOp<$ty>>,)+) {
+            fn changed_paths(&self) -> Vec<FieldPath> {
+                let mut paths = vec![];
+                $(if self.$idx.is_some() {
+                    paths.push(FieldPath::index($idx));
+                })+
+                paths
+            }
+        }
+
+        impl<$($ty),+> Rundo for ValueType<($($ty,)+)>
+        where
+            $($ty: Clone + PartialEq + Debug),+
+        {
+            type Op = ($(Option<VtOp<$ty>>,)+);
+
+            fn dirty(&self) -> bool {
+                match self.origin {
+                    Some(ref ori) => *ori != self.value,
+                    None => false,
+                }
+            }
+
+            fn reset(&mut self) {
+                self.origin = None;
+            }
+
+            fn change_op(&mut self) -> Option<Self::Op> {
+                match self.origin {
+                    Some(ref ori) if *ori != self.value => {
+                        Some(($(elem_op(&ori.$idx, &self.value.$idx),)+))
+                    }
+                    _ => None,
+                }
+            }
+
+            fn back(&mut self, op: &Self::Op) {
+                $(if let Some(ref op) = op.$idx {
+                    debug_assert_eq!(self.value.$idx, op.curr);
+                    self.value.$idx = op.prev.clone();
+                })+
+                self.reset();
+            }
+
+            fn forward(&mut self, op: &Self::Op) {
+                $(if let Some(ref op) = op.$idx {
+                    debug_assert_eq!(op.prev, self.value.$idx);
+                    self.value.$idx = op.curr.clone();
+                })+
+                self.reset();
+            }
+        }
+    )*};
+}
+
+tuple_rundo! {
+    (A 0)
+    (A 0, B 1)
+    (A 0, B 1, C 2)
+    (A 0, B 1, C 2, D 3)
+    (A 0, B 1, C 2, D 3, E 4)
+    (A 0, B 1, C 2, D 3, E 4, F 5)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn array() {
+        let mut pos = ValueType::from([1.0, 2.0, 3.0]);
+        pos[1] = 5.0;
+        let op = pos.change_op().unwrap();
+        assert_eq!(op.len(), 1);
+        assert_eq!((op[0].0, op[0].1.prev, op[0].1.curr), (1, 2.0, 5.0));
+
+        pos.reset();
+        pos[2] = 6.0;
+        let op = op.compose(&pos.change_op().unwrap());
+        pos.back(&op);
+        assert_eq!(*pos, [1.0, 2.0, 3.0]);
+        pos.forward(&op);
+        assert_eq!(*pos, [1.0, 5.0, 6.0]);
+
+        // concurrent changes to different elements are both kept.
+        let mut other = ValueType::from([1.0, 2.0, 3.0]);
+        other[0] = 0.0;
+        let other_op = other.change_op().unwrap();
+        pos.forward(&other_op.transform(&op).unwrap());
+        assert_eq!(*pos, [0.0, 5.0, 6.0]);
+    }
+
+    #[test]
+    fn tuple() {
+        let mut range = ValueType::from((1u32, 5u32, "a"));
+        range.1 = 8;
+        let op = range.change_op().unwrap();
+        assert!(op.0.is_none() && op.2.is_none());
+
+        range.back(&op);
+        assert_eq!(*range, (1, 5, "a"));
+        range.forward(&op);
+        assert_eq!(*range, (1, 8, "a"));
+
+        let mut other = ValueType::from((1u32, 5u32, "a"));
+        other.1 = 9;
+        assert!(other.change_op().unwrap().transform(&op).is_none());
+    }
+}
@@ -0,0 +1,236 @@
+use std::fmt::Debug;
+use std::ops::{Deref, DerefMut};
+
+use field_path::FieldPath;
+use super::{ChangedPaths, Compose, Invert, MergePolicy, Rundo, Transform};
+
+/// Numbers which can be changed by an additive delta. Integer deltas are
+/// wrapping, so decrease an unsigned number is also a delta.
+pub trait Delta: Copy + PartialEq + Debug {
+    fn zero() -> Self;
+    /// `self + delta`
+    fn add_delta(&self, delta: &Self) -> Self;
+    /// the delta from `other` to `self`, as `self - other`.
+    fn delta_from(&self, other: &Self) -> Self;
+}
+
+macro_rules! int_delta {
+    ($($ty: ty),*) => {$(
+        impl Delta for $ty {
+            fn zero() -> Self {
+                0
+            }
+
+            fn add_delta(&self, delta: &Self) -> Self {
+                self.wrapping_add(*delta)
+            }
+
+            fn delta_from(&self, other: &Self) -> Self {
+                self.wrapping_sub(*other)
+            }
+        }
+    )*};
+}
+
+macro_rules! float_delta {
+    ($($ty: ty),*) => {$(
+        impl Delta for $ty {
+            fn zero() -> Self {
+                0.0
+            }
+
+            fn add_delta(&self, delta: &Self) -> Self {
+                self + delta
+            }
+
+            fn delta_from(&self, other: &Self) -> Self {
+                self - other
+            }
+        }
+    )*};
+}
+
+int_delta!(i8, u8, i16, u16, i32, u32, i64, u64, isize, usize);
+float_delta!(f32, f64);
+
+/// A number type record its change as a delta, not the prev and curr value.
+/// Concurrent deltas are all kept when merged, like two users nudge the same
+/// counter or position.
+///
+/// A float delta is rounded, so undo a float change by its delta may not
+/// restore the exact prev value, like `0.1` changed to `100.1` is undone to
+/// `0.09999999999999432`. Don't use it for a float must be restored exactly.
+#[derive(Clone)]
+pub struct DeltaType<T>
+where
+    T: Delta,
+{
+    pub(crate) value: T,
+    pub(crate) origin: Option<T>,
+}
+
+/// impl Deref let DeltaType<T> transparent to user access T value.
+impl<T> Deref for DeltaType<T>
+where
+    T: Delta,
+{
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+/// when user try to get a mut refercence, Rundo know what changed in the later.
+impl<T> DerefMut for DeltaType<T>
+where
+    T: Delta,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        if self.origin.is_none() {
+            self.origin = Some(self.value);
+        }
+        &mut self.value
+    }
+}
+
+impl<T> From<T> for DeltaType<T>
+where
+    T: Delta,
+{
+    fn from(from: T) -> Self {
+        DeltaType {
+            value: from,
+            origin: None,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct DeltaOp<T> {
+    pub delta: T,
+}
+
+impl<T> Compose for DeltaOp<T>
+where
+    T: Delta,
+{
+    fn compose(&self, next: &Self) -> Self {
+        DeltaOp {
+            delta: self.delta.add_delta(&next.delta),
+        }
+    }
+}
+
+impl<T> Invert for DeltaOp<T>
+where
+    T: Delta,
+{
+    fn invert(&self) -> Self {
+        DeltaOp {
+            delta: T::zero().delta_from(&self.delta),
+        }
+    }
+}
+
+/// Deltas are commutative, so a delta keeps itself over any concurrent delta
+/// and never conflict.
+impl<T> Transform for DeltaOp<T>
+where
+    T: Delta,
+{
+    fn transform_by(&self, _other: &Self, _policy: MergePolicy) -> Option<Self> {
+        Some(self.clone())
+    }
+}
+
+impl<T> ChangedPaths for DeltaOp<T> {
+    fn changed_paths(&self) -> Vec<FieldPath> {
+        vec![FieldPath::root()]
+    }
+}
+
+impl<T> Rundo for DeltaType<T>
+where
+    T: Delta,
+{
+    type Op = DeltaOp<T>;
+
+    fn dirty(&self) -> bool {
+        match self.origin {
+            Some(ref ori) => *ori != self.value,
+            None => false,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.origin = None;
+    }
+
+    fn change_op(&mut self) -> Option<Self::Op> {
+        match self.origin {
+            Some(ref ori) if ori != &self.value => Some(DeltaOp {
+                delta: self.value.delta_from(ori),
+            }),
+            _ => None,
+        }
+    }
+
+    fn back(&mut self, op: &Self::Op) {
+        self.forward(&op.invert());
+    }
+
+    fn forward(&mut self, op: &Self::Op) {
+        self.value = self.value.add_delta(&op.delta);
+        self.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsigned_delta() {
+        let mut count = DeltaType::from(5u32);
+        *count -= 2;
+        let op = count.change_op().unwrap();
+        count.reset();
+
+        count.back(&op);
+        assert_eq!(*count, 5);
+        count.forward(&op);
+        assert_eq!(*count, 3);
+        assert_eq!(op.compose(&op.invert()), DeltaOp { delta: 0 });
+    }
+
+    #[test]
+    fn float_delta_drift() {
+        let mut pos = DeltaType::from(0.1f64);
+        *pos = 100.1;
+        let op = pos.change_op().unwrap();
+        pos.reset();
+
+        // the rounded delta can't restore the exact value.
+        pos.back(&op);
+        assert!(*pos != 0.1);
+        assert!((*pos - 0.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn concurrent_delta() {
+        let mut local = DeltaType::from(10i32);
+        let mut remote = local.clone();
+        *local += 2;
+        *remote -= 5;
+        let op = local.change_op().unwrap();
+        let remote_op = remote.change_op().unwrap();
+        local.reset();
+        remote.reset();
+
+        // both nudges are kept no matter which policy.
+        local.forward(&remote_op.transform(&op).unwrap());
+        remote.forward(&op.transform_by(&remote_op, MergePolicy::FirstWriterWins).unwrap());
+        assert_eq!(*local, 7);
+        assert_eq!(*remote, 7);
+    }
+}
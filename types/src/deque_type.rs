@@ -0,0 +1,505 @@
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::ops::{Deref, DerefMut};
+
+use field_path::FieldPath;
+use super::{ChangedPaths, Compose, Invert, MergePolicy, Rundo, Transform};
+
+/// An element op of deque, unlike `StrOP`, every op's index is relative to the
+/// deque changed by the ops before it.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub enum DequeOP<T> {
+    Ins { idx: usize, value: T },
+    Del { idx: usize, value: T },
+    Chg { idx: usize, from: T, to: T },
+}
+
+impl<T> DequeOP<T> {
+    pub fn index(&self) -> usize {
+        match self {
+            &DequeOP::Ins { idx, .. } | &DequeOP::Del { idx, .. } | &DequeOP::Chg { idx, .. } => {
+                idx
+            }
+        }
+    }
+}
+
+impl<T> DequeOP<T>
+where
+    T: Clone + PartialEq,
+{
+    fn with_index(&self, idx: usize) -> Self {
+        let mut op = self.clone();
+        match op {
+            DequeOP::Ins { idx: ref mut i, .. }
+            | DequeOP::Del { idx: ref mut i, .. }
+            | DequeOP::Chg { idx: ref mut i, .. } => *i = idx,
+        }
+        op
+    }
+
+    fn invert(&self) -> Self {
+        match self {
+            &DequeOP::Ins { idx, ref value } => DequeOP::Del {
+                idx,
+                value: value.clone(),
+            },
+            &DequeOP::Del { idx, ref value } => DequeOP::Ins {
+                idx,
+                value: value.clone(),
+            },
+            &DequeOP::Chg {
+                idx,
+                ref from,
+                ref to,
+            } => DequeOP::Chg {
+                idx,
+                from: to.clone(),
+                to: from.clone(),
+            },
+        }
+    }
+
+    /// transform `self` over `other`, both applied to the same deque. Return
+    /// `Some(None)` if `self` has nothing to do after `other`, `None` if conflict.
+    fn transform_by(&self, other: &Self, policy: MergePolicy) -> Option<Option<Self>> {
+        let idx = self.index();
+        let shifted = |delta: isize| Some(Some(self.with_index((idx as isize + delta) as usize)));
+        match (self, other) {
+            (&DequeOP::Ins { .. }, &DequeOP::Ins { idx: other_idx, .. }) => {
+                if idx < other_idx {
+                    Some(Some(self.clone()))
+                } else if idx > other_idx {
+                    shifted(1)
+                } else {
+                    // the winner placed before.
+                    match policy {
+                        MergePolicy::LastWriterWins => Some(Some(self.clone())),
+                        MergePolicy::FirstWriterWins => shifted(1),
+                        MergePolicy::Conflict => None,
+                    }
+                }
+            }
+            (_, &DequeOP::Ins { idx: other_idx, .. }) if idx >= other_idx => shifted(1),
+            (&DequeOP::Ins { .. }, &DequeOP::Del { idx: other_idx, .. }) if idx > other_idx => {
+                shifted(-1)
+            }
+            (&DequeOP::Del { .. }, &DequeOP::Del { idx: other_idx, .. }) if idx == other_idx => {
+                Some(None)
+            }
+            (&DequeOP::Chg { ref to, .. }, &DequeOP::Del { idx: other_idx, .. })
+                if idx == other_idx =>
+            {
+                // changed an element deleted by other, insert it back if win.
+                match policy {
+                    MergePolicy::LastWriterWins => Some(Some(DequeOP::Ins {
+                        idx,
+                        value: to.clone(),
+                    })),
+                    MergePolicy::FirstWriterWins => Some(None),
+                    MergePolicy::Conflict => None,
+                }
+            }
+            (_, &DequeOP::Del { idx: other_idx, .. }) if idx > other_idx => shifted(-1),
+            (&DequeOP::Del { .. }, &DequeOP::Chg { idx: other_idx, ref to, .. })
+                if idx == other_idx =>
+            {
+                match policy {
+                    MergePolicy::LastWriterWins => Some(Some(DequeOP::Del {
+                        idx,
+                        value: to.clone(),
+                    })),
+                    MergePolicy::FirstWriterWins => Some(None),
+                    MergePolicy::Conflict => None,
+                }
+            }
+            (
+                &DequeOP::Chg { ref to, .. },
+                &DequeOP::Chg {
+                    idx: other_idx,
+                    to: ref other_to,
+                    ..
+                },
+            ) if idx == other_idx =>
+            {
+                if to == other_to {
+                    Some(None)
+                } else {
+                    match policy {
+                        MergePolicy::LastWriterWins => Some(Some(DequeOP::Chg {
+                            idx,
+                            from: other_to.clone(),
+                            to: to.clone(),
+                        })),
+                        MergePolicy::FirstWriterWins => Some(None),
+                        MergePolicy::Conflict => None,
+                    }
+                }
+            }
+            _ => Some(Some(self.clone())),
+        }
+    }
+}
+
+/// An undoable `VecDeque`, push and pop at both ends and the indexed edits
+/// record the element ops directly, changes through `DerefMut` are diffed
+/// with a backup.
+#[derive(Clone)]
+pub struct DequeType<T>
+where
+    T: Clone + PartialEq,
+{
+    pub(crate) value: VecDeque<T>,
+    pub(crate) origin: Option<VecDeque<T>>,
+    pub(crate) edits: Option<Vec<DequeOP<T>>>,
+}
+
+/// impl Deref let DequeType transparent to user access the deque.
+impl<T> Deref for DequeType<T>
+where
+    T: Clone + PartialEq,
+{
+    type Target = VecDeque<T>;
+    fn deref(&self) -> &VecDeque<T> {
+        &self.value
+    }
+}
+
+/// when user try to get a mut refercence, backup the deque to diff later.
+impl<T> DerefMut for DequeType<T>
+where
+    T: Clone + PartialEq,
+{
+    fn deref_mut(&mut self) -> &mut VecDeque<T> {
+        if self.origin.is_none() {
+            self.origin = Some(self.value.clone());
+        }
+        &mut self.value
+    }
+}
+
+impl<T> From<VecDeque<T>> for DequeType<T>
+where
+    T: Clone + PartialEq,
+{
+    fn from(from: VecDeque<T>) -> Self {
+        DequeType {
+            value: from,
+            origin: None,
+            edits: None,
+        }
+    }
+}
+
+impl<T> DequeType<T>
+where
+    T: Clone + PartialEq,
+{
+    pub fn push_back(&mut self, value: T) {
+        let idx = self.value.len();
+        self.insert(idx, value);
+    }
+
+    pub fn push_front(&mut self, value: T) {
+        self.insert(0, value);
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        match self.value.len() {
+            0 => None,
+            len => self.remove(len - 1),
+        }
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.remove(0)
+    }
+
+    /// Inserts an element at `idx`, like `VecDeque::insert`.
+    pub fn insert(&mut self, idx: usize, value: T) {
+        self.value.insert(idx, value.clone());
+        self.record(DequeOP::Ins { idx, value });
+    }
+
+    /// Removes the element at `idx`, like `VecDeque::remove`.
+    pub fn remove(&mut self, idx: usize) -> Option<T> {
+        let value = self.value.remove(idx);
+        if let Some(ref value) = value {
+            self.record(DequeOP::Del {
+                idx,
+                value: value.clone(),
+            });
+        }
+        value
+    }
+
+    /// Replaces the element at `idx`, return the old one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn set(&mut self, idx: usize, value: T) -> T {
+        let from = ::std::mem::replace(&mut self.value[idx], value.clone());
+        if from != value {
+            self.record(DequeOP::Chg {
+                idx,
+                from: from.clone(),
+                to: value,
+            });
+        }
+        from
+    }
+
+    /// the recorded edits composed with the diff of the backup, `None` if
+    /// they cancel each other.
+    fn pending_op(&self) -> Option<Vec<DequeOP<T>>> {
+        let diffed = self.origin
+            .as_ref()
+            .map(|ori| diff_ops(ori, &self.value));
+        match self.edits.compose(&diffed) {
+            Some(ref ops) if ops.is_empty() => None,
+            ops => ops,
+        }
+    }
+
+    fn record(&mut self, op: DequeOP<T>) {
+        // the origin value will be diffed, include this edit.
+        if self.origin.is_some() {
+            return;
+        }
+        let op = vec![op];
+        self.edits = Some(match self.edits.take() {
+            Some(edits) => edits.compose(&op),
+            None => op,
+        });
+    }
+}
+
+/// the ops change `ori` to `curr`, skip the same head and tail, and change the
+/// elements between them in place.
+fn diff_ops<T>(ori: &VecDeque<T>, curr: &VecDeque<T>) -> Vec<DequeOP<T>>
+where
+    T: Clone + PartialEq,
+{
+    let head = ori.iter().zip(curr.iter()).take_while(|&(a, b)| a == b).count();
+    let tail = ori.iter()
+        .rev()
+        .zip(curr.iter().rev())
+        .take(::std::cmp::min(ori.len(), curr.len()) - head)
+        .take_while(|&(a, b)| a == b)
+        .count();
+    let ori_len = ori.len() - head - tail;
+    let curr_len = curr.len() - head - tail;
+    let mut ops = vec![];
+    for i in head..head + ::std::cmp::min(ori_len, curr_len) {
+        ops.push(DequeOP::Chg {
+            idx: i,
+            from: ori[i].clone(),
+            to: curr[i].clone(),
+        });
+    }
+    let idx = head + ::std::cmp::min(ori_len, curr_len);
+    for i in curr_len..ori_len {
+        ops.push(DequeOP::Del {
+            idx,
+            value: ori[head + i].clone(),
+        });
+    }
+    for i in ori_len..curr_len {
+        ops.push(DequeOP::Ins {
+            idx: head + i,
+            value: curr[head + i].clone(),
+        });
+    }
+    ops
+}
+
+/// An op followed by its inverse, like an insert and a remove of the same
+/// element, cancel each other.
+impl<T> Compose for Vec<DequeOP<T>>
+where
+    T: Clone + PartialEq,
+{
+    fn compose(&self, next: &Self) -> Self {
+        let mut ops = self.clone();
+        for op in next {
+            if ops.last() == Some(&op.invert()) {
+                ops.pop();
+            } else {
+                ops.push(op.clone());
+            }
+        }
+        ops
+    }
+}
+
+impl<T> Invert for Vec<DequeOP<T>>
+where
+    T: Clone + PartialEq,
+{
+    fn invert(&self) -> Self {
+        self.iter().rev().map(|op| op.invert()).collect()
+    }
+}
+
+impl<T> Transform for Vec<DequeOP<T>>
+where
+    T: Clone + PartialEq,
+{
+    fn transform_by(&self, other: &Self, policy: MergePolicy) -> Option<Self> {
+        let mut other = other.clone();
+        let mut ops = Vec::with_capacity(self.len());
+        for op in self {
+            // transform op over other, and rebase other after op for the next.
+            let mut op = Some(op.clone());
+            let mut rebased = Vec::with_capacity(other.len());
+            for other_op in other {
+                match op {
+                    Some(curr) => {
+                        op = curr.transform_by(&other_op, policy)?;
+                        rebased.extend(other_op.transform_by(&curr, policy.reverse())?);
+                    }
+                    None => rebased.push(other_op),
+                }
+            }
+            other = rebased;
+            ops.extend(op);
+        }
+        Some(ops)
+    }
+}
+
+/// The index of every element op, relative to the deque changed by the ops
+/// before it like the op itself.
+impl<T> ChangedPaths for Vec<DequeOP<T>> {
+    fn changed_paths(&self) -> Vec<FieldPath> {
+        let mut paths: Vec<FieldPath> = vec![];
+        for op in self {
+            let path = FieldPath::index(op.index());
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        paths
+    }
+}
+
+impl<T> Rundo for DequeType<T>
+where
+    T: Clone + PartialEq + Debug,
+{
+    type Op = Vec<DequeOP<T>>;
+
+    fn dirty(&self) -> bool {
+        self.pending_op().is_some()
+    }
+
+    fn reset(&mut self) {
+        self.origin = None;
+        self.edits = None;
+    }
+
+    fn change_op(&mut self) -> Option<Self::Op> {
+        self.pending_op()
+    }
+
+    fn back(&mut self, op: &Self::Op) {
+        self.forward(&op.invert());
+    }
+
+    fn forward(&mut self, op: &Self::Op) {
+        for uop in op {
+            match uop {
+                &DequeOP::Ins { idx, ref value } => self.value.insert(idx, value.clone()),
+                &DequeOP::Del { idx, ref value } => {
+                    let removed = self.value.remove(idx);
+                    debug_assert_eq!(removed.as_ref(), Some(value));
+                }
+                &DequeOP::Chg {
+                    idx,
+                    ref from,
+                    ref to,
+                } => {
+                    debug_assert_eq!(&self.value[idx], from);
+                    self.value[idx] = to.clone();
+                }
+            }
+        }
+        self.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deque(items: &[i32]) -> DequeType<i32> {
+        DequeType::from(items.iter().cloned().collect::<VecDeque<_>>())
+    }
+
+    #[test]
+    fn deque_edit() {
+        let mut recent = deque(&[1, 2, 3]);
+        recent.push_front(0);
+        recent.pop_back();
+        recent.set(1, 5);
+        recent.push_back(4);
+        assert_eq!(recent.iter().cloned().collect::<Vec<_>>(), vec![0, 5, 2, 4]);
+
+        let op = recent.change_op().unwrap();
+        recent.back(&op);
+        assert_eq!(recent.iter().cloned().collect::<Vec<_>>(), vec![1, 2, 3]);
+        recent.forward(&op);
+        assert_eq!(recent.iter().cloned().collect::<Vec<_>>(), vec![0, 5, 2, 4]);
+
+        // changes through DerefMut are diffed.
+        recent.reset();
+        recent.retain(|x| *x != 5);
+        recent.push_back(6);
+        let op = recent.change_op().unwrap();
+        recent.back(&op);
+        assert_eq!(recent.iter().cloned().collect::<Vec<_>>(), vec![0, 5, 2, 4]);
+        recent.forward(&op);
+        assert_eq!(recent.iter().cloned().collect::<Vec<_>>(), vec![0, 2, 4, 6]);
+    }
+
+    #[test]
+    fn deque_edits_cancel() {
+        let mut recent = deque(&[1]);
+        recent.push_back(2);
+        recent.pop_back();
+        assert!(!recent.dirty());
+        assert!(recent.change_op().is_none());
+
+        (*recent).push_back(3);
+        (*recent).pop_back();
+        assert!(!recent.dirty());
+    }
+
+    #[test]
+    fn deque_transform() {
+        let mut local = deque(&[1, 2, 3]);
+        let mut remote = local.clone();
+        local.push_front(0);
+        local.set(2, 5);
+        remote.pop_front();
+        remote.push_back(4);
+        let op = local.change_op().unwrap();
+        let remote_op = remote.change_op().unwrap();
+        local.reset();
+        remote.reset();
+
+        let policy = MergePolicy::LastWriterWins;
+        local.forward(&remote_op.transform_by(&op, policy).unwrap());
+        remote.forward(&op.transform_by(&remote_op, policy.reverse()).unwrap());
+        assert_eq!(*local, *remote);
+        assert_eq!(local.iter().cloned().collect::<Vec<_>>(), vec![0, 5, 3, 4]);
+
+        let mut other = deque(&[1, 2]);
+        other.set(0, 7);
+        let other_op = other.change_op().unwrap();
+        let mut other = deque(&[1, 2]);
+        other.set(0, 8);
+        assert!(other.change_op().unwrap().transform(&other_op).is_none());
+    }
+}
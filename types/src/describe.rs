@@ -0,0 +1,182 @@
+//! Describe ops for human, only the changed values are described, like
+//! `point.a: 1 → 2`. String edits are inline diffs like `name: @0[-he-]{+ye+}`,
+//! removed text in `[-...-]` and inserted in `{+...+}`. The offsets are the
+//! byte offsets `StrOP` stores, in the text before the op. They are not
+//! converted to chars or UTF-16 units, which needs the text, see
+//! `string_index::from_byte_ops`.
+
+use std::fmt::Debug;
+
+use bytes_type::BytePatch;
+use delta_type::DeltaOp;
+use deque_type::DequeOP;
+use field_path::FieldPath;
+use primitive_type::VtOp;
+use set_type::SetOP;
+use string_type::StrOP;
+
+/// Ops can describe their changes, one line for every changed value.
+pub trait Describe {
+    /// append the lines of the changes to `lines`, `path` is the path of the
+    /// value owns the op.
+    fn describe_at(&self, path: &FieldPath, lines: &mut Vec<String>);
+
+    /// a line for every changed value, like `point.a: 1 → 2`.
+    fn describe(&self) -> Vec<String> {
+        let mut lines = vec![];
+        self.describe_at(&FieldPath::root(), &mut lines);
+        lines
+    }
+}
+
+fn line(path: &FieldPath, change: String) -> String {
+    if path.is_root() {
+        change
+    } else {
+        format!("{}: {}", path, change)
+    }
+}
+
+impl<T: Debug> Describe for VtOp<T> {
+    fn describe_at(&self, path: &FieldPath, lines: &mut Vec<String>) {
+        lines.push(line(path, format!("{:?} → {:?}", self.prev, self.curr)));
+    }
+}
+
+/// every edit is `@offset` and its diff, the offset is in bytes.
+impl Describe for Vec<StrOP> {
+    fn describe_at(&self, path: &FieldPath, lines: &mut Vec<String>) {
+        let edits = self.iter()
+            .map(|op| match op {
+                &StrOP::Ins { idx, ref value } => format!("@{}{{+{}+}}", idx, value),
+                &StrOP::Del { idx, ref value } => format!("@{}[-{}-]", idx, value),
+                &StrOP::Chg {
+                    idx,
+                    ref from,
+                    ref to,
+                } => format!("@{}[-{}-]{{+{}+}}", idx, from, to),
+            })
+            .collect::<Vec<_>>();
+        lines.push(line(path, edits.join(" ")));
+    }
+}
+
+impl<T: Debug> Describe for DeltaOp<T> {
+    fn describe_at(&self, path: &FieldPath, lines: &mut Vec<String>) {
+        lines.push(line(path, format!("+= {:?}", self.delta)));
+    }
+}
+
+impl<T: Debug> Describe for Vec<SetOP<T>> {
+    fn describe_at(&self, path: &FieldPath, lines: &mut Vec<String>) {
+        let items = self.iter()
+            .map(|op| match op {
+                &SetOP::Add(ref item) => format!("+{:?}", item),
+                &SetOP::Remove(ref item) => format!("-{:?}", item),
+            })
+            .collect::<Vec<_>>();
+        lines.push(line(path, items.join(" ")));
+    }
+}
+
+impl<T: Debug> Describe for Vec<DequeOP<T>> {
+    fn describe_at(&self, path: &FieldPath, lines: &mut Vec<String>) {
+        for op in self {
+            let change = match op {
+                &DequeOP::Ins { ref value, .. } => format!("insert {:?}", value),
+                &DequeOP::Del { ref value, .. } => format!("remove {:?}", value),
+                &DequeOP::Chg { ref from, ref to, .. } => format!("{:?} → {:?}", from, to),
+            };
+            lines.push(line(&path.child_index(op.index()), change));
+        }
+    }
+}
+
+impl Describe for Vec<BytePatch> {
+    fn describe_at(&self, path: &FieldPath, lines: &mut Vec<String>) {
+        let hex = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join("")
+        };
+        let patches = self.iter()
+            .map(|patch| match (patch.removed.is_empty(), patch.inserted.is_empty()) {
+                (false, true) => format!("@{}[-{}-]", patch.offset, hex(&patch.removed)),
+                (true, false) => format!("@{}{{+{}+}}", patch.offset, hex(&patch.inserted)),
+                _ => format!(
+                    "@{}[-{}-]{{+{}+}}",
+                    patch.offset,
+                    hex(&patch.removed),
+                    hex(&patch.inserted)
+                ),
+            })
+            .collect::<Vec<_>>();
+        lines.push(line(path, patches.join(" ")));
+    }
+}
+
+impl<T: Debug> Describe for Vec<(usize, VtOp<T>)> {
+    fn describe_at(&self, path: &FieldPath, lines: &mut Vec<String>) {
+        for &(idx, ref op) in self {
+            op.describe_at(&path.child_index(idx), lines);
+        }
+    }
+}
+
+macro_rules! tuple_describe {
+    ($(($($ty: ident $idx: tt),+))*) => {$(
+        impl<$($ty: Debug),+> Describe for ($(Option<VtOp<$ty>>,)+) {
+            fn describe_at(&self, path: &FieldPath, lines: &mut Vec<String>) {
+                $(if let Some(ref op) = self.$idx {
+                    op.describe_at(&path.child_index($idx), lines);
+                })+
+            }
+        }
+    )*};
+}
+
+tuple_describe! {
+    (A 0)
+    (A 0, B 1)
+    (A 0, B 1, C 2)
+    (A 0, B 1, C 2, D 3)
+    (A 0, B 1, C 2, D 3, E 4)
+    (A 0, B 1, C 2, D 3, E 4, F 5)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use deque_type::DequeType;
+    use string_type::StringType;
+    use Rundo;
+
+    #[test]
+    fn string_diff() {
+        let mut name = StringType::from("hello".to_string());
+        name.replace_range(0..2, "ye");
+        name.push_str("!");
+        let op = name.change_op().unwrap();
+        assert_eq!(op.describe(), vec!["@0[-he-]{+ye+} @5{+!+}"]);
+    }
+
+    #[test]
+    fn deque_lines() {
+        let mut list = DequeType::from(VecDeque::from(vec![1, 2]));
+        list.push_back(3);
+        list.set(0, 5);
+        let op = list.change_op().unwrap();
+        let mut lines = vec![];
+        op.describe_at(&FieldPath::root().child_field("items"), &mut lines);
+        assert_eq!(lines, vec!["items[2]: insert 3", "items[0]: 1 → 5"]);
+    }
+}
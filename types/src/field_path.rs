@@ -0,0 +1,69 @@
+use std::fmt;
+
+/// A step of `FieldPath`, a struct field or an element index.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum PathSeg {
+    Field(String),
+    Index(usize),
+}
+
+/// The path of a changed value from the root data, displayed as
+/// `point.a` or `items[3].name`. An empty path is the root itself.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct FieldPath {
+    pub segs: Vec<PathSeg>,
+}
+
+impl FieldPath {
+    pub fn root() -> Self {
+        FieldPath { segs: vec![] }
+    }
+
+    pub fn index(idx: usize) -> Self {
+        FieldPath {
+            segs: vec![PathSeg::Index(idx)],
+        }
+    }
+
+    /// this path in the field `name` of a struct.
+    pub fn in_field(mut self, name: &str) -> Self {
+        self.segs.insert(0, PathSeg::Field(name.to_string()));
+        self
+    }
+
+    /// the path of the field `name` in this path.
+    pub fn child_field(&self, name: &str) -> Self {
+        let mut path = self.clone();
+        path.segs.push(PathSeg::Field(name.to_string()));
+        path
+    }
+
+    /// the path of the element `idx` in this path.
+    pub fn child_index(&self, idx: usize) -> Self {
+        let mut path = self.clone();
+        path.segs.push(PathSeg::Index(idx));
+        path
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.segs.is_empty()
+    }
+
+    /// if `self` is `other` or inside it, like `point.a` starts with `point`.
+    pub fn starts_with(&self, other: &FieldPath) -> bool {
+        self.segs.starts_with(&other.segs)
+    }
+}
+
+impl fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, seg) in self.segs.iter().enumerate() {
+            match seg {
+                &PathSeg::Field(ref name) if i == 0 => write!(f, "{}", name)?,
+                &PathSeg::Field(ref name) => write!(f, ".{}", name)?,
+                &PathSeg::Index(idx) => write!(f, "[{}]", idx)?,
+            }
+        }
+        Ok(())
+    }
+}
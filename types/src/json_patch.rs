@@ -0,0 +1,462 @@
+//! Export ops as [JSON Patch](https://tools.ietf.org/html/rfc6902) and parse
+//! JSON Patch to ops. The paths are JSON Pointers into the serde
+//! representation of data, so a `#[rundo]` struct is an object of its fields.
+//!
+//! A value op is exported as `replace`, a deque op as `add`, `remove` or
+//! `replace` of its element, an array or tuple op as `replace` of its changed
+//! elements, and other ops, like string edits, as `replace` of the whole value.
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+pub use serde_json::Value;
+
+use bytes_type::BytesType;
+use delta_type::{Delta, DeltaType};
+use deque_type::{DequeOP, DequeType};
+use primitive_type::{Primitive, ValueType};
+use rope_type::RopeType;
+use set_type::{Set, SetType};
+use string_type::StringType;
+use super::Rundo;
+
+/// An operation of JSON Patch.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOp {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+impl PatchOp {
+    pub fn path(&self) -> &str {
+        match self {
+            &PatchOp::Add { ref path, .. }
+            | &PatchOp::Remove { ref path }
+            | &PatchOp::Replace { ref path, .. }
+            | &PatchOp::Move { ref path, .. }
+            | &PatchOp::Copy { ref path, .. }
+            | &PatchOp::Test { ref path, .. } => path,
+        }
+    }
+}
+
+/// Errors may occur when parse a JSON Patch.
+#[derive(PartialEq, Debug)]
+pub enum PatchError {
+    /// the path is not a valid JSON Pointer, or not point to a value can be
+    /// patched.
+    PathNotFound(String),
+    /// the value can't be deserialized as the type at the path.
+    InvalidValue(String),
+    /// the operation can't be applied at the path, like remove a struct field,
+    /// or `move` and `copy` which are not supported.
+    Unsupported(String),
+    TestFailed(String),
+}
+
+/// Data can be exported as JSON Patch and be patched.
+pub trait JsonPatch: Rundo {
+    /// append the patch operations of `op` to `patch`, `self` is the data at
+    /// `path` after `op` applied.
+    fn export_patch(&self, op: &Self::Op, path: &str, patch: &mut Vec<PatchOp>);
+
+    /// apply `op` to the value at `tokens` of `self`, the unescaped tokens of
+    /// its path. The change is recorded like other edits.
+    fn apply_patch(&mut self, tokens: &[String], op: &PatchOp) -> Result<(), PatchError>;
+}
+
+/// the JSON Patch of `op`, `data` is the data after `op` applied.
+pub fn to_json_patch<T: JsonPatch>(data: &T, op: &T::Op) -> Vec<PatchOp> {
+    let mut patch = vec![];
+    data.export_patch(op, "", &mut patch);
+    patch
+}
+
+/// parse `patch` to an op of `data`, return `None` if nothing changed. The
+/// patch is applied to `data` to generate the op and then reverted, so
+/// `data` must not have changes not captured yet.
+pub fn from_json_patch<T: JsonPatch>(
+    data: &mut T,
+    patch: &[PatchOp],
+) -> Result<Option<T::Op>, PatchError> {
+    assert!(
+        !data.dirty(),
+        "parse json patch on data with changes not captured is not allowed."
+    );
+    let mut res = Ok(());
+    for op in patch {
+        res = parse_pointer(op.path()).and_then(|tokens| data.apply_patch(&tokens, op));
+        if res.is_err() {
+            break;
+        }
+    }
+    let op = data.change_op();
+    if let Some(ref op) = op {
+        data.back(op);
+    }
+    data.reset();
+    res.map(|_| op)
+}
+
+/// split a JSON Pointer to unescaped tokens.
+pub fn parse_pointer(pointer: &str) -> Result<Vec<String>, PatchError> {
+    if pointer.is_empty() {
+        return Ok(vec![]);
+    }
+    if !pointer.starts_with('/') {
+        return Err(PatchError::PathNotFound(pointer.to_string()));
+    }
+    Ok(pointer[1..]
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+/// the pointer of `token` in the value at `path`.
+pub fn child_pointer(path: &str, token: &str) -> String {
+    format!("{}/{}", path, token.replace('~', "~0").replace('/', "~1"))
+}
+
+fn to_value<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).expect("rundo serialize value to json failed")
+}
+
+fn from_value<T: DeserializeOwned>(value: &Value) -> Result<T, PatchError> {
+    serde_json::from_value(value.clone()).map_err(|err| PatchError::InvalidValue(err.to_string()))
+}
+
+fn replace<T: Serialize>(path: &str, value: &T) -> PatchOp {
+    PatchOp::Replace {
+        path: path.to_string(),
+        value: to_value(value),
+    }
+}
+
+fn test<T: Serialize>(curr: &T, op: &PatchOp, value: &Value) -> Result<(), PatchError> {
+    if to_value(curr) == *value {
+        Ok(())
+    } else {
+        Err(PatchError::TestFailed(op.path().to_string()))
+    }
+}
+
+/// the new value if `op` replaces the whole `curr`, `None` if `op` is a test
+/// and passed.
+fn whole_value<T>(curr: &T, tokens: &[String], op: &PatchOp) -> Result<Option<T>, PatchError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    if !tokens.is_empty() {
+        return Err(PatchError::PathNotFound(op.path().to_string()));
+    }
+    match op {
+        &PatchOp::Add { ref value, .. } | &PatchOp::Replace { ref value, .. } => {
+            from_value(value).map(Some)
+        }
+        &PatchOp::Test { ref value, .. } => test(curr, op, value).map(|_| None),
+        _ => Err(PatchError::Unsupported(op.path().to_string())),
+    }
+}
+
+/// the element index of `tokens`, which has only one token less than `len`.
+fn elem_index(tokens: &[String], len: usize, op: &PatchOp) -> Result<usize, PatchError> {
+    match tokens.first().and_then(|token| token.parse::<usize>().ok()) {
+        Some(idx) if tokens.len() == 1 && idx < len => Ok(idx),
+        _ => Err(PatchError::PathNotFound(op.path().to_string())),
+    }
+}
+
+impl<T> JsonPatch for ValueType<T>
+where
+    T: Clone + PartialEq + Debug + Primitive + Serialize + DeserializeOwned,
+{
+    fn export_patch(&self, _op: &Self::Op, path: &str, patch: &mut Vec<PatchOp>) {
+        patch.push(replace(path, &self.value));
+    }
+
+    fn apply_patch(&mut self, tokens: &[String], op: &PatchOp) -> Result<(), PatchError> {
+        if let Some(value) = whole_value(&self.value, tokens, op)? {
+            **self = value;
+        }
+        Ok(())
+    }
+}
+
+impl JsonPatch for StringType {
+    fn export_patch(&self, _op: &Self::Op, path: &str, patch: &mut Vec<PatchOp>) {
+        patch.push(replace(path, &self.value));
+    }
+
+    fn apply_patch(&mut self, tokens: &[String], op: &PatchOp) -> Result<(), PatchError> {
+        if let Some(value) = whole_value(&self.value, tokens, op)? {
+            **self = value;
+        }
+        Ok(())
+    }
+}
+
+impl JsonPatch for RopeType {
+    fn export_patch(&self, _op: &Self::Op, path: &str, patch: &mut Vec<PatchOp>) {
+        patch.push(replace(path, &self.value.to_string()));
+    }
+
+    fn apply_patch(&mut self, tokens: &[String], op: &PatchOp) -> Result<(), PatchError> {
+        if let Some(value) = whole_value::<String>(&self.value.to_string(), tokens, op)? {
+            self.replace_range(.., &value);
+        }
+        Ok(())
+    }
+}
+
+impl<T> JsonPatch for DeltaType<T>
+where
+    T: Delta + Serialize + DeserializeOwned,
+{
+    fn export_patch(&self, _op: &Self::Op, path: &str, patch: &mut Vec<PatchOp>) {
+        patch.push(replace(path, &self.value));
+    }
+
+    fn apply_patch(&mut self, tokens: &[String], op: &PatchOp) -> Result<(), PatchError> {
+        if let Some(value) = whole_value(&self.value, tokens, op)? {
+            **self = value;
+        }
+        Ok(())
+    }
+}
+
+/// A set is an array in JSON, its items have no stable index, so it's
+/// patched as a whole.
+impl<S> JsonPatch for SetType<S>
+where
+    S: Set + Serialize + DeserializeOwned,
+{
+    fn export_patch(&self, _op: &Self::Op, path: &str, patch: &mut Vec<PatchOp>) {
+        patch.push(replace(path, &self.value));
+    }
+
+    fn apply_patch(&mut self, tokens: &[String], op: &PatchOp) -> Result<(), PatchError> {
+        if let Some(value) = whole_value(&self.value, tokens, op)? {
+            **self = value;
+        }
+        Ok(())
+    }
+}
+
+impl JsonPatch for BytesType {
+    fn export_patch(&self, _op: &Self::Op, path: &str, patch: &mut Vec<PatchOp>) {
+        patch.push(replace(path, &self.value));
+    }
+
+    fn apply_patch(&mut self, tokens: &[String], op: &PatchOp) -> Result<(), PatchError> {
+        if let Some(value) = whole_value(&self.value, tokens, op)? {
+            **self = value;
+        }
+        Ok(())
+    }
+}
+
+impl<T> JsonPatch for DequeType<T>
+where
+    T: Clone + PartialEq + Debug + Serialize + DeserializeOwned,
+{
+    fn export_patch(&self, op: &Self::Op, path: &str, patch: &mut Vec<PatchOp>) {
+        for op in op {
+            let path = child_pointer(path, &op.index().to_string());
+            patch.push(match op {
+                &DequeOP::Ins { ref value, .. } => PatchOp::Add {
+                    path,
+                    value: to_value(value),
+                },
+                &DequeOP::Del { .. } => PatchOp::Remove { path },
+                &DequeOP::Chg { ref to, .. } => replace(&path, to),
+            });
+        }
+    }
+
+    fn apply_patch(&mut self, tokens: &[String], op: &PatchOp) -> Result<(), PatchError> {
+        if tokens.is_empty() {
+            if let Some(value) = whole_value::<VecDeque<T>>(&self.value, tokens, op)? {
+                **self = value;
+            }
+            return Ok(());
+        }
+        let len = self.value.len();
+        match op {
+            &PatchOp::Add { ref value, .. } => {
+                let idx = if tokens.len() == 1 && tokens[0] == "-" {
+                    len
+                } else {
+                    elem_index(tokens, len + 1, op)?
+                };
+                self.insert(idx, from_value(value)?);
+            }
+            &PatchOp::Remove { .. } => {
+                self.remove(elem_index(tokens, len, op)?);
+            }
+            &PatchOp::Replace { ref value, .. } => {
+                let idx = elem_index(tokens, len, op)?;
+                self.set(idx, from_value(value)?);
+            }
+            &PatchOp::Test { ref value, .. } => {
+                test(&self.value[elem_index(tokens, len, op)?], op, value)?;
+            }
+            _ => return Err(PatchError::Unsupported(op.path().to_string())),
+        }
+        Ok(())
+    }
+}
+
+macro_rules! array_patch {
+    ($($n: expr),*) => {$(
+        impl<T> JsonPatch for ValueType<[T; $n]>
+        where
+            T: Clone + PartialEq + Debug + Serialize + DeserializeOwned,
+        {
+            fn export_patch(&self, op: &Self::Op, path: &str, patch: &mut Vec<PatchOp>) {
+                for &(idx, ref op) in op {
+                    patch.push(replace(&child_pointer(path, &idx.to_string()), &op.curr));
+                }
+            }
+
+            fn apply_patch(&mut self, tokens: &[String], op: &PatchOp) -> Result<(), PatchError> {
+                if tokens.is_empty() {
+                    let value = whole_value::<Vec<T>>(&self.value.to_vec(), tokens, op)?;
+                    if let Some(value) = value {
+                        if value.len() != $n {
+                            return Err(PatchError::InvalidValue(op.path().to_string()));
+                        }
+                        self.clone_from_slice(&value);
+                    }
+                    return Ok(());
+                }
+                let idx = elem_index(tokens, $n, op)?;
+                if let Some(value) = whole_value(&self.value[idx], &[], op)? {
+                    self[idx] = value;
+                }
+                Ok(())
+            }
+        }
+    )*};
+}
+
+array_patch!(
+    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25,
+    26, 27, 28, 29, 30, 31, 32
+);
+
+macro_rules! tuple_patch {
+    ($(($($ty: ident $idx: tt),+))*) => {$(
+        impl<$($ty),+> JsonPatch for ValueType<($($ty,)+)>
+        where
+            $($ty: Clone + PartialEq + Debug + Serialize + DeserializeOwned),+
+        {
+            fn export_patch(&self, op: &Self::Op, path: &str, patch: &mut Vec<PatchOp>) {
+                $(if let Some(ref op) = op.$idx {
+                    patch.push(replace(&child_pointer(path, stringify!($idx)), &op.curr));
+                })+
+            }
+
+            fn apply_patch(&mut self, tokens: &[String], op: &PatchOp) -> Result<(), PatchError> {
+                if tokens.is_empty() {
+                    if let Some(value) = whole_value(&self.value, tokens, op)? {
+                        **self = value;
+                    }
+                    return Ok(());
+                }
+                let idx = elem_index(tokens, [$($idx),+].len(), op)?;
+                $(if idx == $idx {
+                    if let Some(value) = whole_value(&self.value.$idx, &[], op)? {
+                        self.$idx = value;
+                    }
+                })+
+                Ok(())
+            }
+        }
+    )*};
+}
+
+tuple_patch! {
+    (A 0)
+    (A 0, B 1)
+    (A 0, B 1, C 2)
+    (A 0, B 1, C 2, D 3)
+    (A 0, B 1, C 2, D 3, E 4)
+    (A 0, B 1, C 2, D 3, E 4, F 5)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10)
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pointer() {
+        assert_eq!(parse_pointer("").unwrap(), Vec::<String>::new());
+        assert_eq!(parse_pointer("/a~1b/~01").unwrap(), vec!["a/b", "~1"]);
+        assert!(parse_pointer("a").is_err());
+        assert_eq!(child_pointer("/items", "a/b"), "/items/a~1b");
+    }
+
+    #[test]
+    fn deque_patch() {
+        let mut list = DequeType::from(VecDeque::from(vec![1, 2, 3]));
+        list.push_back(4);
+        list.remove(0);
+        list.set(0, 5);
+        let op = list.change_op().unwrap();
+        let patch = to_json_patch(&list, &op);
+        let json = serde_json::to_string(&patch).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"op":"add","path":"/3","value":4},{"op":"remove","path":"/0"},{"op":"replace","path":"/0","value":5}]"#
+        );
+
+        let mut other = DequeType::from(VecDeque::from(vec![1, 2, 3]));
+        let parsed = from_json_patch(&mut other, &patch).unwrap().unwrap();
+        assert_eq!(*other, VecDeque::from(vec![1, 2, 3]));
+        other.forward(&parsed);
+        assert_eq!(*other, VecDeque::from(vec![5, 3, 4]));
+
+        let wrong: Vec<PatchOp> = serde_json::from_str(r#"[{"op":"remove","path":"/9"}]"#).unwrap();
+        assert!(from_json_patch(&mut other, &wrong).is_err());
+    }
+
+    #[test]
+    fn tuple_patch() {
+        let mut range = ValueType::from((1u32, "a".to_string()));
+        range.0 = 3;
+        let op = range.change_op().unwrap();
+        let patch = to_json_patch(&range, &op);
+        assert_eq!(
+            patch,
+            vec![PatchOp::Replace {
+                path: "/0".to_string(),
+                value: Value::from(3),
+            }]
+        );
+
+        range.back(&op);
+        let test: Vec<PatchOp> =
+            serde_json::from_str(r#"[{"op":"test","path":"/1","value":"b"}]"#).unwrap();
+        assert_eq!(
+            from_json_patch(&mut range, &test).err(),
+            Some(PatchError::TestFailed("/1".to_string()))
+        );
+        let op = from_json_patch(&mut range, &patch).unwrap().unwrap();
+        range.forward(&op);
+        assert_eq!(*range, (3, "a".to_string()));
+    }
+}
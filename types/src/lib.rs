@@ -1,17 +1,51 @@
 extern crate difference;
+extern crate ropey;
+pub extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate unicode_segmentation;
 
+pub mod bytes_type;
+pub mod compound_type;
+pub mod delta_type;
+pub mod deque_type;
+pub mod describe;
+pub mod field_path;
+pub mod json_patch;
 pub mod primitive_type;
+pub mod rope_type;
+mod seg;
+mod serde_impl;
+pub mod set_type;
+pub mod string_index;
 pub mod string_type;
 
 pub mod prelude {
     pub use Rundo;
+    pub use ChangedPaths;
+    pub use Compose;
+    pub use Invert;
+    pub use MergePolicy;
+    pub use SplitOp;
+    pub use Transform;
+    pub use serde;
+    pub use bytes_type::*;
+    pub use delta_type::*;
+    pub use deque_type::*;
+    pub use describe::*;
+    pub use field_path::*;
+    pub use json_patch::*;
     pub use primitive_type::*;
+    pub use rope_type::*;
+    pub use set_type::*;
+    pub use string_index::*;
     pub use string_type::*;
 }
 
 pub const IMPLED_RUNDO: [&str; 15] = [
     "bool", "char", "i8", "u8", "i16", "u16", "i32", "u32", "i64", "u64", "f32", "f64", "isize",
-    "usize", "string",
+    "usize", "String",
 ];
 
 /// Every rundo node must implement Rundo trait to support undo/redo.
@@ -31,3 +65,107 @@ pub trait Rundo {
     /// Go to the next version of the data should be with a Op.
     fn forward(&mut self, op: &Self::Op);
 }
+
+/// Ops can be composed, `a.compose(&b)` generate one op which has the same
+/// effect as apply `a` and then apply `b`.
+pub trait Compose {
+    fn compose(&self, next: &Self) -> Self;
+}
+
+impl<T> Compose for Option<T>
+where
+    T: Compose + Clone,
+{
+    fn compose(&self, next: &Self) -> Self {
+        match (self, next) {
+            (&Some(ref op), &Some(ref next)) => Some(op.compose(next)),
+            (&Some(ref op), &None) => Some(op.clone()),
+            (&None, &Some(ref next)) => Some(next.clone()),
+            (&None, &None) => None,
+        }
+    }
+}
+
+/// Ops can be inverted, apply `op.invert()` will undo what `op` done,
+/// so `back(op)` is the same as `forward(op.invert())`.
+pub trait Invert {
+    fn invert(&self) -> Self;
+}
+
+impl<T> Invert for Option<T>
+where
+    T: Invert,
+{
+    fn invert(&self) -> Self {
+        self.as_ref().map(|op| op.invert())
+    }
+}
+
+/// How to resolve two concurrent ops which changed the same value to different
+/// results, used when transform ops.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum MergePolicy {
+    /// the op transformed wins, since it will be applied last.
+    LastWriterWins,
+    /// the op transformed over wins, since it was applied first.
+    FirstWriterWins,
+    /// treat it as conflict, the transform failed.
+    Conflict,
+}
+
+impl MergePolicy {
+    /// The policy to transform the other side, keep the same winner.
+    pub fn reverse(&self) -> MergePolicy {
+        match *self {
+            MergePolicy::LastWriterWins => MergePolicy::FirstWriterWins,
+            MergePolicy::FirstWriterWins => MergePolicy::LastWriterWins,
+            MergePolicy::Conflict => MergePolicy::Conflict,
+        }
+    }
+}
+
+/// Ops can be transformed over a concurrent op. Both `self` and `other` are
+/// generated from the same data, `self.transform_by(&other, policy)` returns
+/// an op which keep the intent of `self` but can be applied after `other`.
+/// Return `None` if the two ops conflict.
+pub trait Transform: Sized {
+    fn transform_by(&self, other: &Self, policy: MergePolicy) -> Option<Self>;
+
+    /// transform and treat any value changed by both ops as conflict.
+    fn transform(&self, other: &Self) -> Option<Self> {
+        self.transform_by(other, MergePolicy::Conflict)
+    }
+}
+
+impl<T> Transform for Option<T>
+where
+    T: Transform + Clone,
+{
+    fn transform_by(&self, other: &Self, policy: MergePolicy) -> Option<Self> {
+        match (self, other) {
+            (&Some(ref op), &Some(ref other)) => op.transform_by(other, policy).map(Some),
+            (&Some(ref op), &None) => Some(Some(op.clone())),
+            (&None, _) => Some(None),
+        }
+    }
+}
+
+/// Ops of a struct can be split by a field path, like `["canvas", "color"]`.
+pub trait SplitOp: Sized {
+    /// split into the changes of the field at `path` and the others, `None` if
+    /// nothing changed. An empty `path` selects the whole op.
+    fn split(&self, path: &[&str]) -> (Option<Self>, Option<Self>);
+
+    /// if `path` is a field of the value owns the op, an empty `path` is the
+    /// value itself, which is the only path of a value without fields.
+    fn has_path(path: &[&str]) -> bool {
+        path.is_empty()
+    }
+}
+
+/// Ops know which values they changed, so observers can react to them.
+pub trait ChangedPaths {
+    /// the paths of changed values relative to the value owns the op, a root
+    /// path means the value itself.
+    fn changed_paths(&self) -> Vec<field_path::FieldPath>;
+}
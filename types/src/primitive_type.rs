@@ -2,14 +2,16 @@ use std::cmp::PartialEq;
 use std::convert::From;
 use std::convert::{AsMut, AsRef};
 use std::fmt::Debug;
-use std::ops::{Deref, DerefMut};
+use std::ops::{Deref, DerefMut, Sub};
 
-use super::Rundo;
+use field_path::FieldPath;
+use super::{ChangedPaths, Compose, Invert, MergePolicy, Rundo, Transform};
 
 /// Value type like a memory undo/redo type.
 /// Rundo will clone its origin value as a backup, so Clone must be implemented.
 /// **Be careful use it for struct or other big size type**,
 /// OpType is design for this scenario, or you must implment your custrom rundo type.
+#[derive(Clone)]
 pub struct ValueType<T>
 where
     T: Clone + PartialEq,
@@ -55,10 +57,155 @@ where
     }
 }
 
-#[derive(Debug)]
+impl<T> ValueType<T>
+where
+    T: Clone + PartialEq,
+{
+    /// detect changes like `dirty`, but compare values by `eq` instead of
+    /// `PartialEq`.
+    pub fn dirty_with<F>(&self, eq: F) -> bool
+    where
+        F: Fn(&T, &T) -> bool,
+    {
+        self.origin.as_ref().map_or(false, |ori| !eq(ori, &self.value))
+    }
+
+    /// generate the change op like `change_op`, but compare values by `eq`
+    /// instead of `PartialEq`.
+    pub fn change_op_with<F>(&self, eq: F) -> Option<VtOp<T>>
+    where
+        F: Fn(&T, &T) -> bool,
+    {
+        match self.origin {
+            Some(ref ori) if !eq(ori, &self.value) => Some(VtOp {
+                prev: ori.clone(),
+                curr: self.value.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// undo the op like `back`, but check the value by `eq` instead of
+    /// `PartialEq`.
+    pub fn back_with<F>(&mut self, op: &VtOp<T>, eq: F)
+    where
+        F: Fn(&T, &T) -> bool,
+    {
+        debug_assert!(eq(&self.value, &op.curr), "the value is not the op's result");
+        self.value = op.prev.clone();
+        self.origin = None;
+    }
+
+    /// redo the op like `forward`, but check the value by `eq` instead of
+    /// `PartialEq`.
+    pub fn forward_with<F>(&mut self, op: &VtOp<T>, eq: F)
+    where
+        F: Fn(&T, &T) -> bool,
+    {
+        debug_assert!(eq(&op.prev, &self.value), "the value is not the op's base");
+        self.value = op.curr.clone();
+        self.origin = None;
+    }
+}
+
+/// Compare the bits of values, so NaN is equal to itself.
+pub trait BitwiseEq {
+    fn bitwise_eq(&self, other: &Self) -> bool;
+}
+
+macro_rules! bitwise_eq {
+    ($($ty: ty),* ; $($float: ty),*) => {
+        $(impl BitwiseEq for $ty {
+            fn bitwise_eq(&self, other: &Self) -> bool {
+                self == other
+            }
+        })*
+        $(impl BitwiseEq for $float {
+            fn bitwise_eq(&self, other: &Self) -> bool {
+                self.to_bits() == other.to_bits()
+            }
+        })*
+    };
+}
+
+bitwise_eq!(bool, char, i8, u8, i16, u16, i32, u32, i64, u64, isize, usize; f32, f64);
+
+/// compare values bitwise, NaN-safe.
+pub fn bitwise_eq<T: BitwiseEq>(a: &T, b: &T) -> bool {
+    a.bitwise_eq(b)
+}
+
+/// two values are equal if their difference is no more than `epsilon`.
+pub fn epsilon_eq<T>(a: &T, b: &T, epsilon: T) -> bool
+where
+    T: PartialOrd + Sub<Output = T> + Copy,
+{
+    if a > b {
+        *a - *b <= epsilon
+    } else {
+        *b - *a <= epsilon
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VtOp<T> {
-    prev: T,
-    curr: T,
+    pub(crate) prev: T,
+    pub(crate) curr: T,
+}
+
+impl<T> Compose for VtOp<T>
+where
+    T: Clone,
+{
+    fn compose(&self, next: &Self) -> Self {
+        VtOp {
+            prev: self.prev.clone(),
+            curr: next.curr.clone(),
+        }
+    }
+}
+
+impl<T> Invert for VtOp<T>
+where
+    T: Clone,
+{
+    fn invert(&self) -> Self {
+        VtOp {
+            prev: self.curr.clone(),
+            curr: self.prev.clone(),
+        }
+    }
+}
+
+impl<T> Transform for VtOp<T>
+where
+    T: Clone + PartialEq,
+{
+    fn transform_by(&self, other: &Self, policy: MergePolicy) -> Option<Self> {
+        let changed = self.prev != self.curr;
+        let other_changed = other.prev != other.curr;
+        let curr = if !changed {
+            &other.curr
+        } else if !other_changed || self.curr == other.curr {
+            &self.curr
+        } else {
+            match policy {
+                MergePolicy::LastWriterWins => &self.curr,
+                MergePolicy::FirstWriterWins => &other.curr,
+                MergePolicy::Conflict => return None,
+            }
+        };
+        Some(VtOp {
+            prev: other.curr.clone(),
+            curr: curr.clone(),
+        })
+    }
+}
+
+impl<T> ChangedPaths for VtOp<T> {
+    fn changed_paths(&self) -> Vec<FieldPath> {
+        vec![FieldPath::root()]
+    }
 }
 
 impl<T> AsMut<T> for ValueType<T>
@@ -103,10 +250,7 @@ where
     type Op = VtOp<T>;
 
     fn dirty(&self) -> bool {
-        match self.origin {
-            Some(ref ori) => *ori != self.value,
-            None => false,
-        }
+        self.dirty_with(PartialEq::eq)
     }
 
     fn reset(&mut self) {
@@ -114,13 +258,7 @@ where
     }
 
     fn change_op(&mut self) -> Option<Self::Op> {
-        match self.origin {
-            Some(ref ori) if ori != &self.value => Some(VtOp {
-                prev: ori.clone(),
-                curr: self.value.clone(),
-            }),
-            _ => None,
-        }
+        self.change_op_with(PartialEq::eq)
     }
 
     fn back(&mut self, op: &Self::Op) {
@@ -185,4 +323,74 @@ mod tests {
     fn i8() {
         type_test!(1i8, 2i8)
     }
+
+    #[test]
+    fn compose() {
+        let mut leaf = ValueType::from(1);
+        *leaf = 2;
+        let op1 = leaf.change_op().unwrap();
+        leaf.reset();
+        *leaf = 3;
+        let op2 = leaf.change_op().unwrap();
+        leaf.reset();
+
+        let op = op1.compose(&op2);
+        assert_eq!(op.prev, 1);
+        assert_eq!(op.curr, 3);
+
+        leaf.back(&op);
+        assert_eq!(*leaf, 1);
+    }
+
+    #[test]
+    fn invert() {
+        let mut leaf = ValueType::from(1);
+        *leaf = 2;
+        let op = leaf.change_op().unwrap();
+        leaf.reset();
+
+        leaf.forward(&op.invert());
+        assert_eq!(*leaf, 1);
+        leaf.back(&op.invert());
+        assert_eq!(*leaf, 2);
+    }
+
+    #[test]
+    fn transform() {
+        let op = VtOp { prev: 1, curr: 2 };
+        let same = VtOp { prev: 1, curr: 1 };
+        let other = VtOp { prev: 1, curr: 3 };
+
+        let t = op.transform(&same).unwrap();
+        assert_eq!((t.prev, t.curr), (1, 2));
+        let t = same.transform(&other).unwrap();
+        assert_eq!((t.prev, t.curr), (3, 3));
+        assert!(op.transform(&other).is_none());
+
+        let t = op.transform_by(&other, MergePolicy::LastWriterWins).unwrap();
+        assert_eq!((t.prev, t.curr), (3, 2));
+        let t = op.transform_by(&other, MergePolicy::FirstWriterWins).unwrap();
+        assert_eq!((t.prev, t.curr), (3, 3));
+    }
+
+    #[test]
+    fn custom_eq() {
+        let mut leaf = ValueType::from(::std::f32::NAN);
+        *leaf = ::std::f32::NAN;
+        assert!(leaf.dirty());
+        assert!(!leaf.dirty_with(bitwise_eq));
+        assert!(leaf.change_op_with(bitwise_eq).is_none());
+
+        let eq = |a: &f64, b: &f64| epsilon_eq(a, b, 1e-6);
+        let mut leaf = ValueType::from(0.3);
+        *leaf = 0.1 + 0.2;
+        assert!(!leaf.dirty_with(eq));
+        *leaf = 0.4;
+        let op = leaf.change_op_with(eq).unwrap();
+        *leaf = 0.1 + 0.3;
+        leaf.back_with(&op, eq);
+        assert_eq!(*leaf, 0.3);
+        leaf.forward_with(&op, eq);
+        assert_eq!(*leaf, 0.4);
+    }
 }
@@ -0,0 +1,317 @@
+use ropey::Rope;
+use std::cmp;
+use std::ops::{Bound, Deref, DerefMut, RangeBounds};
+
+use string_type::{diff_ops, non_empty, StrDiff, StrOP};
+use super::{Compose, Invert, Rundo};
+
+/// A text type backed by rope for large documents. It generates the same ops as
+/// `StringType`, and all indexes are byte offsets like `String`, but
+/// applies an op in O(log n) instead of rebuild the whole string.
+#[derive(Clone)]
+pub struct RopeType {
+    pub(crate) value: Rope,
+    pub(crate) origin: Option<Rope>,
+    pub(crate) edits: Option<Vec<StrOP>>,
+}
+
+/// impl Deref let RopeType transparent to user access the Rope.
+impl Deref for RopeType {
+    type Target = Rope;
+    fn deref(&self) -> &Rope {
+        &self.value
+    }
+}
+
+/// Modify the Rope directly, it's cheap to backup a rope, but the op will be
+/// generated by diff the changed lines.
+impl DerefMut for RopeType {
+    fn deref_mut(&mut self) -> &mut Rope {
+        if self.origin.is_none() {
+            self.origin = Some(self.value.clone());
+        }
+        &mut self.value
+    }
+}
+
+impl From<Rope> for RopeType {
+    fn from(from: Rope) -> Self {
+        RopeType {
+            value: from,
+            origin: None,
+            edits: None,
+        }
+    }
+}
+
+impl From<String> for RopeType {
+    fn from(from: String) -> Self {
+        RopeType::from(Rope::from(from))
+    }
+}
+
+impl<'a> From<&'a str> for RopeType {
+    fn from(from: &'a str) -> Self {
+        RopeType::from(Rope::from_str(from))
+    }
+}
+
+impl RopeType {
+    /// generate the change op like `change_op`, but compare texts split by `diff`.
+    pub fn change_op_by(&self, diff: StrDiff) -> Option<Vec<StrOP>> {
+        let diffed = self
+            .origin
+            .as_ref()
+            .map(|ori| diff_ropes(ori, &self.value, diff));
+        self.edits.compose(&diffed).and_then(non_empty)
+    }
+
+    /// Inserts a string slice at byte position `idx`, like `String::insert_str`.
+    pub fn insert_str(&mut self, idx: usize, string: &str) {
+        let char_idx = self.char_idx(idx);
+        self.value.insert(char_idx, string);
+        self.record(StrOP::Ins {
+            idx,
+            value: string.to_string(),
+        });
+    }
+
+    /// Appends a string slice onto the end, like `String::push_str`.
+    pub fn push_str(&mut self, string: &str) {
+        let idx = self.value.len_bytes();
+        self.insert_str(idx, string);
+    }
+
+    /// Shortens to the byte length `new_len`, like `String::truncate`.
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len < self.value.len_bytes() {
+            let len = self.value.len_bytes();
+            self.replace_range(new_len..len, "");
+        }
+    }
+
+    /// Replaces the byte range with the string, like `String::replace_range`.
+    pub fn replace_range<R>(&mut self, range: R, string: &str)
+    where
+        R: RangeBounds<usize>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n + 1,
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => self.value.len_bytes(),
+        };
+        let (char_start, char_end) = (self.char_idx(start), self.char_idx(end));
+        let from = self.value.slice(char_start..char_end).to_string();
+        self.value.remove(char_start..char_end);
+        self.value.insert(char_start, string);
+        let op = match (from.is_empty(), string.is_empty()) {
+            (true, true) => return,
+            (true, false) => StrOP::Ins {
+                idx: start,
+                value: string.to_string(),
+            },
+            (false, true) => StrOP::Del {
+                idx: start,
+                value: from,
+            },
+            (false, false) => StrOP::Chg {
+                idx: start,
+                from,
+                to: string.to_string(),
+            },
+        };
+        self.record(op);
+    }
+
+    fn char_idx(&self, byte_idx: usize) -> usize {
+        let char_idx = self.value.byte_to_char(byte_idx);
+        assert_eq!(
+            self.value.char_to_byte(char_idx),
+            byte_idx,
+            "byte index is not a char boundary"
+        );
+        char_idx
+    }
+
+    fn record(&mut self, op: StrOP) {
+        // the origin value will be diffed, include this edit.
+        if self.origin.is_some() {
+            return;
+        }
+        let op = vec![op];
+        self.edits = match self.edits.take() {
+            Some(edits) => non_empty(edits.compose(&op)),
+            None => Some(op),
+        };
+    }
+}
+
+/// diff the texts by `diff`, the same lines at the head and tail are skipped
+/// instead of copy the whole ropes to strings. `StrDiff::Replace` replaces the
+/// whole text, so it still compares the whole text.
+fn diff_ropes(ori: &Rope, curr: &Rope, diff: StrDiff) -> Vec<StrOP> {
+    if diff == StrDiff::Replace {
+        return diff_ops(&ori.to_string(), &curr.to_string(), diff);
+    }
+    let (ori_lines, curr_lines) = (ori.len_lines(), curr.len_lines());
+    let len = cmp::min(ori_lines, curr_lines);
+    let head = (0..len)
+        .take_while(|&i| ori.line(i) == curr.line(i))
+        .count();
+    let tail = (0..len - head)
+        .take_while(|&i| ori.line(ori_lines - 1 - i) == curr.line(curr_lines - 1 - i))
+        .count();
+    let rest = |text: &Rope| {
+        let end = text.line_to_char(text.len_lines() - tail);
+        text.slice(text.line_to_char(head)..end).to_string()
+    };
+    let offset = ori.line_to_byte(head);
+    diff_ops(&rest(ori), &rest(curr), diff)
+        .into_iter()
+        .map(|op| op.with_index(op.index() + offset))
+        .collect()
+}
+
+impl Rundo for RopeType {
+    type Op = Vec<StrOP>;
+
+    fn dirty(&self) -> bool {
+        match (&self.origin, &self.edits) {
+            (&None, edits) => edits.is_some(),
+            (&Some(ref ori), &None) => *ori != self.value,
+            _ => self.change_op_by(StrDiff::Char).is_some(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.origin = None;
+        self.edits = None;
+    }
+
+    fn change_op(&mut self) -> Option<Self::Op> {
+        self.change_op_by(StrDiff::Char)
+    }
+
+    fn back(&mut self, op: &Self::Op) {
+        self.forward(&op.invert());
+    }
+
+    fn forward(&mut self, op: &Self::Op) {
+        self.reset();
+        // indexes are offsets in the origin text, apply from the last one so
+        // the former indexes keep valid.
+        for uop in op.iter().rev() {
+            let idx = self.char_idx(uop.index());
+            match uop {
+                &StrOP::Ins { ref value, .. } => self.value.insert(idx, value),
+                &StrOP::Del { ref value, .. } => {
+                    let end = idx + value.chars().count();
+                    debug_assert_eq!(self.value.slice(idx..end), value.as_str());
+                    self.value.remove(idx..end);
+                }
+                &StrOP::Chg {
+                    ref from, ref to, ..
+                } => {
+                    let end = idx + from.chars().count();
+                    debug_assert_eq!(self.value.slice(idx..end), from.as_str());
+                    self.value.remove(idx..end);
+                    self.value.insert(idx, to);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use string_type::StringType;
+
+    #[test]
+    fn edit() {
+        let mut text = RopeType::from("hello world");
+        text.push_str("!");
+        text.replace_range(6..11, "rope");
+        text.insert_str(0, "> ");
+        assert_eq!(*text, "> hello rope!");
+        assert!(text.dirty());
+
+        // the same op as String edits.
+        let op = text.change_op().unwrap();
+        let mut string = StringType::from("hello world".to_string());
+        string.push_str("!");
+        string.replace_range(6..11, "rope");
+        string.insert_str(0, "> ");
+        assert_eq!(op, string.change_op().unwrap());
+
+        text.back(&op);
+        assert_eq!(*text, "hello world");
+        text.forward(&op);
+        assert_eq!(*text, "> hello rope!");
+        assert!(!text.dirty());
+    }
+
+    #[test]
+    fn deref_mut() {
+        let mut text = RopeType::from("你好 world");
+        text.truncate(7);
+        text.insert(3, "rope");
+        assert_eq!(*text, "你好 rope");
+
+        let op = text.change_op().unwrap();
+        assert_eq!(
+            op,
+            vec![StrOP::Chg {
+                idx: 7,
+                from: "world".to_string(),
+                to: "rope".to_string(),
+            }]
+        );
+        text.back(&op);
+        assert_eq!(*text, "你好 world");
+        text.forward(&op);
+        assert_eq!(*text, "你好 rope");
+    }
+
+    #[test]
+    fn cancelled_edit() {
+        let mut text = RopeType::from("hello");
+        text.push_str(" world");
+        text.truncate(5);
+        assert!(!text.dirty());
+        assert_eq!(text.change_op(), None);
+
+        text.insert(0, "> ");
+        text.remove(0..2);
+        assert!(!text.dirty());
+        assert_eq!(text.change_op(), None);
+    }
+
+    #[test]
+    fn diff_changed_lines() {
+        let mut text = RopeType::from("one\ntwo\nthree\n");
+        text.remove(4..7);
+        text.insert(4, "2");
+        assert_eq!(
+            text.change_op_by(StrDiff::Line),
+            Some(vec![StrOP::Chg {
+                idx: 4,
+                from: "two\n".to_string(),
+                to: "2\n".to_string(),
+            }])
+        );
+        assert_eq!(
+            text.change_op(),
+            Some(vec![StrOP::Chg {
+                idx: 4,
+                from: "two".to_string(),
+                to: "2".to_string(),
+            }])
+        );
+    }
+}
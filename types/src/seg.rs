@@ -0,0 +1,243 @@
+use std;
+
+/// The content can be inserted or deleted by a seg, like a string or bytes.
+pub(crate) trait SegText: Ord + Sized {
+    fn len(&self) -> usize;
+    /// split at `at`, return the head and the rest.
+    fn split_at(self, at: usize) -> (Self, Self);
+    fn concat(self, other: Self) -> Self;
+}
+
+impl SegText for String {
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn split_at(mut self, at: usize) -> (Self, Self) {
+        let rest = self.split_off(at);
+        (self, rest)
+    }
+
+    fn concat(self, other: Self) -> Self {
+        self + &other
+    }
+}
+
+impl SegText for Vec<u8> {
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn split_at(mut self, at: usize) -> (Self, Self) {
+        let rest = self.split_off(at);
+        (self, rest)
+    }
+
+    fn concat(mut self, other: Self) -> Self {
+        self.extend(other);
+        self
+    }
+}
+
+/// An op described as a walk over the origin text, it's easier to compose ops
+/// in this form.
+#[derive(PartialEq, Debug)]
+pub(crate) enum Seg<T> {
+    Retain(usize),
+    Ins(T),
+    Del(T),
+}
+
+impl<T: SegText> Seg<T> {
+    fn len(&self) -> usize {
+        match self {
+            &Seg::Retain(n) => n,
+            &Seg::Ins(ref text) | &Seg::Del(ref text) => text.len(),
+        }
+    }
+
+    /// split the seg at `at`, return the head part and the rest part if any.
+    fn split(self, at: usize) -> (Seg<T>, Option<Seg<T>>) {
+        if at >= self.len() {
+            return (self, None);
+        }
+        match self {
+            Seg::Retain(n) => (Seg::Retain(at), Some(Seg::Retain(n - at))),
+            Seg::Ins(text) => {
+                let (head, rest) = text.split_at(at);
+                (Seg::Ins(head), Some(Seg::Ins(rest)))
+            }
+            Seg::Del(text) => {
+                let (head, rest) = text.split_at(at);
+                (Seg::Del(head), Some(Seg::Del(rest)))
+            }
+        }
+    }
+}
+
+/// push seg and merge it with the last one, a delete seg always placed before
+/// an adjacent insert seg, so they can be merged to a `StrOP::Chg` later.
+pub(crate) fn push_seg<T: SegText>(segs: &mut Vec<Seg<T>>, seg: Seg<T>) {
+    if seg.len() == 0 {
+        return;
+    }
+    match (segs.pop(), seg) {
+        (Some(Seg::Retain(a)), Seg::Retain(b)) => segs.push(Seg::Retain(a + b)),
+        (Some(Seg::Ins(a)), Seg::Ins(b)) => segs.push(Seg::Ins(a.concat(b))),
+        (Some(Seg::Del(a)), Seg::Del(b)) => segs.push(Seg::Del(a.concat(b))),
+        (Some(Seg::Ins(a)), Seg::Del(b)) => {
+            push_seg(segs, Seg::Del(b));
+            segs.push(Seg::Ins(a));
+        }
+        (last, seg) => {
+            if let Some(last) = last {
+                segs.push(last);
+            }
+            segs.push(seg);
+        }
+    }
+}
+
+pub(crate) fn compose_segs<T: SegText>(first: Vec<Seg<T>>, next: Vec<Seg<T>>) -> Vec<Seg<T>> {
+    let mut segs = Vec::with_capacity(first.len() + next.len());
+    let mut first = first.into_iter();
+    let mut next = next.into_iter();
+    let mut a = first.next();
+    let mut b = next.next();
+    loop {
+        match (a.take(), b.take()) {
+            (None, None) => break,
+            // deleted by first op, next op never see it.
+            (Some(Seg::Del(text)), rest) => {
+                push_seg(&mut segs, Seg::Del(text));
+                a = first.next();
+                b = rest;
+            }
+            // inserted by next op, first op never see it.
+            (rest, Some(Seg::Ins(text))) => {
+                push_seg(&mut segs, Seg::Ins(text));
+                a = rest;
+                b = next.next();
+            }
+            (Some(seg), None) => {
+                push_seg(&mut segs, seg);
+                a = first.next();
+            }
+            (None, Some(seg)) => {
+                push_seg(&mut segs, seg);
+                b = next.next();
+            }
+            (Some(sa), Some(sb)) => {
+                let at = std::cmp::min(sa.len(), sb.len());
+                let (sa, rest_a) = sa.split(at);
+                let (sb, rest_b) = sb.split(at);
+                match (sa, sb) {
+                    (Seg::Retain(n), Seg::Retain(_)) => push_seg(&mut segs, Seg::Retain(n)),
+                    (Seg::Retain(_), Seg::Del(text)) => push_seg(&mut segs, Seg::Del(text)),
+                    (Seg::Ins(text), Seg::Retain(_)) => push_seg(&mut segs, Seg::Ins(text)),
+                    // inserted by first op and deleted by next op, just cancel.
+                    (Seg::Ins(_), Seg::Del(_)) => {}
+                    _ => unreachable!(),
+                }
+                a = rest_a.or_else(|| first.next());
+                b = rest_b.or_else(|| next.next());
+            }
+        }
+    }
+    segs
+}
+
+/// transform `segs` over `other`, both of them are generated from the same
+/// text. Text deleted by both ops is only deleted once, if both ops insert at
+/// the same position, the smaller text placed first, so the result is
+/// deterministic no matter which side is transformed.
+pub(crate) fn transform_segs<T: SegText>(segs: Vec<Seg<T>>, other: Vec<Seg<T>>) -> Vec<Seg<T>> {
+    let mut res = Vec::with_capacity(segs.len() + other.len());
+    let mut segs = segs.into_iter();
+    let mut other = other.into_iter();
+    let mut a = segs.next();
+    let mut b = other.next();
+    loop {
+        match (a.take(), b.take()) {
+            (None, None) => break,
+            (Some(Seg::Ins(text)), Some(Seg::Ins(other_text))) => {
+                if text <= other_text {
+                    push_seg(&mut res, Seg::Ins(text));
+                    a = segs.next();
+                    b = Some(Seg::Ins(other_text));
+                } else {
+                    push_seg(&mut res, Seg::Retain(other_text.len()));
+                    a = Some(Seg::Ins(text));
+                    b = other.next();
+                }
+            }
+            (Some(Seg::Ins(text)), rest) => {
+                push_seg(&mut res, Seg::Ins(text));
+                a = segs.next();
+                b = rest;
+            }
+            // skip the text inserted by other.
+            (rest, Some(Seg::Ins(text))) => {
+                push_seg(&mut res, Seg::Retain(text.len()));
+                a = rest;
+                b = other.next();
+            }
+            (Some(seg), None) => {
+                push_seg(&mut res, seg);
+                a = segs.next();
+            }
+            (None, Some(_)) => b = other.next(),
+            (Some(sa), Some(sb)) => {
+                let at = std::cmp::min(sa.len(), sb.len());
+                let (sa, rest_a) = sa.split(at);
+                let (sb, rest_b) = sb.split(at);
+                match (sa, sb) {
+                    (Seg::Retain(n), Seg::Retain(_)) => push_seg(&mut res, Seg::Retain(n)),
+                    (Seg::Del(text), Seg::Retain(_)) => push_seg(&mut res, Seg::Del(text)),
+                    // already deleted by other.
+                    (Seg::Retain(_), Seg::Del(_)) | (Seg::Del(_), Seg::Del(_)) => {}
+                    _ => unreachable!(),
+                }
+                a = rest_a.or_else(|| segs.next());
+                b = rest_b.or_else(|| other.next());
+            }
+        }
+    }
+    res
+}
+
+/// the origin ranges deleted by `segs`, and the positions inserted at.
+fn touched<T: SegText>(segs: &[Seg<T>]) -> (Vec<(usize, usize)>, Vec<usize>) {
+    let mut dels = vec![];
+    let mut inss = vec![];
+    let mut pos = 0;
+    for seg in segs {
+        match seg {
+            &Seg::Retain(n) => pos += n,
+            &Seg::Del(ref text) => {
+                dels.push((pos, pos + text.len()));
+                pos += text.len();
+            }
+            &Seg::Ins(_) => inss.push(pos),
+        }
+    }
+    (dels, inss)
+}
+
+/// if two ops generated from the same text edit the same place, they both
+/// delete some text, both insert at a position, or one inserts into the text
+/// deleted by the other.
+pub(crate) fn segs_overlap<T: SegText>(segs: &[Seg<T>], other: &[Seg<T>]) -> bool {
+    let (dels_a, inss_a) = touched(segs);
+    let (dels_b, inss_b) = touched(other);
+    let ins_in = |inss: &[usize], dels: &[(usize, usize)]| {
+        inss.iter()
+            .any(|&at| dels.iter().any(|&(start, end)| start < at && at < end))
+    };
+    dels_a
+        .iter()
+        .any(|&(sa, ea)| dels_b.iter().any(|&(sb, eb)| sa < eb && sb < ea))
+        || inss_a.iter().any(|at| inss_b.contains(at))
+        || ins_in(&inss_a, &dels_b)
+        || ins_in(&inss_b, &dels_a)
+}
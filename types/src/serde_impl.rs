@@ -0,0 +1,123 @@
+//! The undoable types are serialized as their values, so a struct generated by
+//! `#[rundo]` can derive `Serialize` and `Deserialize`. A deserialized value
+//! has no change to record.
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use bytes_type::BytesType;
+use delta_type::{Delta, DeltaType};
+use deque_type::DequeType;
+use primitive_type::ValueType;
+use rope_type::RopeType;
+use set_type::{Set, SetType};
+use string_type::StringType;
+
+impl<T> Serialize for ValueType<T>
+where
+    T: Clone + PartialEq + Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for ValueType<T>
+where
+    T: Clone + PartialEq + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(ValueType::from)
+    }
+}
+
+impl Serialize for StringType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for StringType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(StringType::from)
+    }
+}
+
+impl Serialize for RopeType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.value)
+    }
+}
+
+impl<'de> Deserialize<'de> for RopeType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(RopeType::from)
+    }
+}
+
+impl<T> Serialize for DeltaType<T>
+where
+    T: Delta + Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for DeltaType<T>
+where
+    T: Delta + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(DeltaType::from)
+    }
+}
+
+impl<S> Serialize for SetType<S>
+where
+    S: Set + Serialize,
+{
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+impl<'de, S> Deserialize<'de> for SetType<S>
+where
+    S: Set + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        S::deserialize(deserializer).map(SetType::from)
+    }
+}
+
+impl<T> Serialize for DequeType<T>
+where
+    T: Clone + PartialEq + Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for DequeType<T>
+where
+    T: Clone + PartialEq + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        VecDeque::deserialize(deserializer).map(DequeType::from)
+    }
+}
+
+impl Serialize for BytesType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BytesType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::deserialize(deserializer).map(BytesType::from)
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::ops::{Deref, DerefMut};
+
+use field_path::FieldPath;
+use super::{ChangedPaths, Compose, Invert, MergePolicy, Rundo, Transform};
+
+/// The common set operations `SetType` needs, implemented for `HashSet` and
+/// `BTreeSet`.
+pub trait Set: Clone + Default {
+    type Item: Clone + PartialEq + Debug;
+    fn insert_item(&mut self, item: Self::Item) -> bool;
+    fn remove_item(&mut self, item: &Self::Item) -> bool;
+    /// the items in `self` but not in `other`.
+    fn difference_items(&self, other: &Self) -> Vec<Self::Item>;
+}
+
+impl<T> Set for HashSet<T>
+where
+    T: Clone + Eq + Hash + Debug,
+{
+    type Item = T;
+
+    fn insert_item(&mut self, item: T) -> bool {
+        self.insert(item)
+    }
+
+    fn remove_item(&mut self, item: &T) -> bool {
+        self.remove(item)
+    }
+
+    fn difference_items(&self, other: &Self) -> Vec<T> {
+        self.difference(other).cloned().collect()
+    }
+}
+
+impl<T> Set for BTreeSet<T>
+where
+    T: Clone + Ord + Debug,
+{
+    type Item = T;
+
+    fn insert_item(&mut self, item: T) -> bool {
+        self.insert(item)
+    }
+
+    fn remove_item(&mut self, item: &T) -> bool {
+        self.remove(item)
+    }
+
+    fn difference_items(&self, other: &Self) -> Vec<T> {
+        self.difference(other).cloned().collect()
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub enum SetOP<T> {
+    Add(T),
+    Remove(T),
+}
+
+impl<T> SetOP<T> {
+    pub fn item(&self) -> &T {
+        match self {
+            &SetOP::Add(ref item) | &SetOP::Remove(ref item) => item,
+        }
+    }
+}
+
+/// An undoable set, `insert`, `remove` and `clear` record the item ops
+/// directly, changes through `DerefMut` are diffed with a backup.
+#[derive(Clone)]
+pub struct SetType<S>
+where
+    S: Set,
+{
+    pub(crate) value: S,
+    pub(crate) origin: Option<S>,
+    pub(crate) edits: Option<Vec<SetOP<S::Item>>>,
+}
+
+/// impl Deref let SetType transparent to user access the set.
+impl<S> Deref for SetType<S>
+where
+    S: Set,
+{
+    type Target = S;
+    fn deref(&self) -> &S {
+        &self.value
+    }
+}
+
+/// when user try to get a mut refercence, backup the set to diff later.
+impl<S> DerefMut for SetType<S>
+where
+    S: Set,
+{
+    fn deref_mut(&mut self) -> &mut S {
+        if self.origin.is_none() {
+            self.origin = Some(self.value.clone());
+        }
+        &mut self.value
+    }
+}
+
+impl<S> From<S> for SetType<S>
+where
+    S: Set,
+{
+    fn from(from: S) -> Self {
+        SetType {
+            value: from,
+            origin: None,
+            edits: None,
+        }
+    }
+}
+
+impl<S> SetType<S>
+where
+    S: Set,
+{
+    /// Adds an item, return if the item was not present, like `HashSet::insert`.
+    pub fn insert(&mut self, item: S::Item) -> bool {
+        let inserted = self.value.insert_item(item.clone());
+        if inserted {
+            self.record(SetOP::Add(item));
+        }
+        inserted
+    }
+
+    /// Removes an item, return if the item was present, like `HashSet::remove`.
+    pub fn remove(&mut self, item: &S::Item) -> bool {
+        let removed = self.value.remove_item(item);
+        if removed {
+            self.record(SetOP::Remove(item.clone()));
+        }
+        removed
+    }
+
+    /// Removes all items.
+    pub fn clear(&mut self) {
+        for item in self.value.difference_items(&S::default()) {
+            self.remove(&item);
+        }
+    }
+
+    /// the recorded edits composed with the diff of the backup, `None` if
+    /// they cancel each other.
+    fn pending_op(&self) -> Option<Vec<SetOP<S::Item>>> {
+        let diffed = self.origin.as_ref().map(|ori| {
+            let mut ops = ori.difference_items(&self.value)
+                .into_iter()
+                .map(SetOP::Remove)
+                .collect::<Vec<_>>();
+            ops.extend(self.value.difference_items(ori).into_iter().map(SetOP::Add));
+            ops
+        });
+        match self.edits.compose(&diffed) {
+            Some(ref ops) if ops.is_empty() => None,
+            ops => ops,
+        }
+    }
+
+    fn record(&mut self, op: SetOP<S::Item>) {
+        // the origin value will be diffed, include this edit.
+        if self.origin.is_some() {
+            return;
+        }
+        let op = vec![op];
+        self.edits = Some(match self.edits.take() {
+            Some(edits) => edits.compose(&op),
+            None => op,
+        });
+    }
+}
+
+/// Items ops are not ordered, an add and a remove of the same item cancel
+/// each other.
+impl<T> Compose for Vec<SetOP<T>>
+where
+    T: Clone + PartialEq,
+{
+    fn compose(&self, next: &Self) -> Self {
+        let mut ops = self.clone();
+        for op in next {
+            match ops.iter().position(|o| o.item() == op.item()) {
+                Some(idx) => {
+                    ops.remove(idx);
+                }
+                None => ops.push(op.clone()),
+            }
+        }
+        ops
+    }
+}
+
+impl<T> Invert for Vec<SetOP<T>>
+where
+    T: Clone,
+{
+    fn invert(&self) -> Self {
+        self.iter()
+            .map(|op| match op {
+                &SetOP::Add(ref item) => SetOP::Remove(item.clone()),
+                &SetOP::Remove(ref item) => SetOP::Add(item.clone()),
+            })
+            .collect()
+    }
+}
+
+/// Concurrent ops from the same set can only add or remove an item both,
+/// which has been done by `other`, so never conflict.
+impl<T> Transform for Vec<SetOP<T>>
+where
+    T: Clone + PartialEq,
+{
+    fn transform_by(&self, other: &Self, _policy: MergePolicy) -> Option<Self> {
+        Some(
+            self.iter()
+                .filter(|op| !other.contains(op))
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+/// A set has no index, the set itself changed.
+impl<T> ChangedPaths for Vec<SetOP<T>> {
+    fn changed_paths(&self) -> Vec<FieldPath> {
+        vec![FieldPath::root()]
+    }
+}
+
+impl<S> Rundo for SetType<S>
+where
+    S: Set,
+{
+    type Op = Vec<SetOP<S::Item>>;
+
+    fn dirty(&self) -> bool {
+        self.pending_op().is_some()
+    }
+
+    fn reset(&mut self) {
+        self.origin = None;
+        self.edits = None;
+    }
+
+    fn change_op(&mut self) -> Option<Self::Op> {
+        self.pending_op()
+    }
+
+    fn back(&mut self, op: &Self::Op) {
+        self.forward(&op.invert());
+    }
+
+    fn forward(&mut self, op: &Self::Op) {
+        for uop in op {
+            let applied = match uop {
+                &SetOP::Add(ref item) => self.value.insert_item(item.clone()),
+                &SetOP::Remove(ref item) => self.value.remove_item(item),
+            };
+            debug_assert!(applied, "{:?} can't apply", uop);
+        }
+        self.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_edit() {
+        let mut tags = SetType::from(vec!["a", "b"].into_iter().collect::<HashSet<_>>());
+        tags.insert("c");
+        tags.remove(&"a");
+        tags.insert("a");
+        (*tags).insert("d");
+        let op = tags.change_op().unwrap();
+        assert_eq!(op, vec![SetOP::Add("c"), SetOP::Add("d")]);
+
+        tags.back(&op);
+        assert_eq!(tags.len(), 2);
+        tags.forward(&op);
+        assert!(tags.contains("c") && tags.contains("d"));
+
+        tags.clear();
+        assert!(tags.is_empty());
+        let op = tags.change_op().unwrap();
+        tags.back(&op);
+        assert_eq!(tags.len(), 4);
+    }
+
+    #[test]
+    fn set_edits_cancel() {
+        let mut tags = SetType::from(vec![1].into_iter().collect::<BTreeSet<_>>());
+        tags.insert(2);
+        tags.remove(&2);
+        assert!(!tags.dirty());
+        assert!(tags.change_op().is_none());
+
+        (*tags).insert(3);
+        (*tags).remove(&3);
+        assert!(!tags.dirty());
+    }
+
+    #[test]
+    fn set_transform() {
+        let mut local = SetType::from(vec![1, 2].into_iter().collect::<BTreeSet<_>>());
+        let mut remote = local.clone();
+        local.insert(3);
+        local.remove(&1);
+        remote.insert(3);
+        remote.insert(4);
+        let op = local.change_op().unwrap();
+        let remote_op = remote.change_op().unwrap();
+        local.reset();
+        remote.reset();
+
+        local.forward(&remote_op.transform(&op).unwrap());
+        remote.forward(&op.transform(&remote_op).unwrap());
+        assert_eq!(*local, *remote);
+        assert_eq!(local.iter().cloned().collect::<Vec<_>>(), vec![2, 3, 4]);
+    }
+}
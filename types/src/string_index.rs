@@ -0,0 +1,179 @@
+//! `StrOP` generated by Rundo use byte offset as index, but editors may count
+//! string by chars, graphemes or UTF-16 code units (like JavaScript and LSP).
+//! This module convert ops between these units and validate them, so an op
+//! will never slice string at the middle of a char.
+
+use string_type::StrOP;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The unit of `StrOP` index.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum IndexUnit {
+    Byte,
+    Char,
+    Grapheme,
+    Utf16,
+}
+
+/// Errors may occur when validate or convert string ops, with the position
+/// of the bad op.
+#[derive(PartialEq, Debug)]
+pub enum StrOpError {
+    /// the index is out of the string, or not at a boundary of the unit.
+    InvalidIndex(usize),
+    /// the text removed by the op not match the string.
+    TextMismatch(usize),
+    /// the op is before or overlap with the previous op, ops must be ascending.
+    Unordered(usize),
+}
+
+/// all boundaries in text as pairs of (index by unit, byte offset), include
+/// the end of text.
+fn boundaries(text: &str, unit: IndexUnit) -> Vec<(usize, usize)> {
+    let mut bounds = Vec::with_capacity(text.len() + 1);
+    let end = match unit {
+        IndexUnit::Byte => {
+            bounds.extend(text.char_indices().map(|(i, _)| (i, i)));
+            text.len()
+        }
+        IndexUnit::Char => {
+            bounds.extend(text.char_indices().enumerate().map(|(n, (i, _))| (n, i)));
+            bounds.len()
+        }
+        IndexUnit::Grapheme => {
+            bounds.extend(text.grapheme_indices(true).enumerate().map(|(n, (i, _))| (n, i)));
+            bounds.len()
+        }
+        IndexUnit::Utf16 => {
+            let mut n = 0;
+            for (i, c) in text.char_indices() {
+                bounds.push((n, i));
+                n += c.len_utf16();
+            }
+            n
+        }
+    };
+    bounds.push((end, text.len()));
+    bounds
+}
+
+/// check if byte offset `ops` can be applied to `text`.
+pub fn validate(text: &str, ops: &[StrOP]) -> Result<(), StrOpError> {
+    let mut base = 0;
+    for (n, op) in ops.iter().enumerate() {
+        let idx = op.index();
+        if idx < base {
+            return Err(StrOpError::Unordered(n));
+        }
+        if !text.is_char_boundary(idx) {
+            return Err(StrOpError::InvalidIndex(n));
+        }
+        base = match op {
+            &StrOP::Ins { .. } => idx,
+            &StrOP::Del { ref value, .. } | &StrOP::Chg { from: ref value, .. } => {
+                let end = idx + value.len();
+                if text.get(idx..end) != Some(value.as_str()) {
+                    return Err(StrOpError::TextMismatch(n));
+                }
+                end
+            }
+        };
+    }
+    Ok(())
+}
+
+/// convert `ops` index by `unit` to byte offset ops, which can be applied to
+/// `text` directly. The result ops are validated.
+pub fn to_byte_ops(text: &str, ops: &[StrOP], unit: IndexUnit) -> Result<Vec<StrOP>, StrOpError> {
+    let bounds = boundaries(text, unit);
+    let ops = ops.iter()
+        .enumerate()
+        .map(|(n, op)| {
+            bounds
+                .binary_search_by_key(&op.index(), |b| b.0)
+                .map(|i| op.with_index(bounds[i].1))
+                .map_err(|_| StrOpError::InvalidIndex(n))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    validate(text, &ops)?;
+    Ok(ops)
+}
+
+/// convert byte offset `ops` of `text` to ops index by `unit`.
+pub fn from_byte_ops(text: &str, ops: &[StrOP], unit: IndexUnit) -> Result<Vec<StrOP>, StrOpError> {
+    validate(text, ops)?;
+    let bounds = boundaries(text, unit);
+    ops.iter()
+        .enumerate()
+        .map(|(n, op)| {
+            bounds
+                .binary_search_by_key(&op.index(), |b| b.1)
+                .map(|i| op.with_index(bounds[i].0))
+                .map_err(|_| StrOpError::InvalidIndex(n))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ins(idx: usize, value: &str) -> StrOP {
+        StrOP::Ins {
+            idx,
+            value: value.to_string(),
+        }
+    }
+
+    fn del(idx: usize, value: &str) -> StrOP {
+        StrOP::Del {
+            idx,
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn utf16() {
+        let text = "a😀b";
+        let ops = to_byte_ops(text, &[ins(3, "c")], IndexUnit::Utf16).unwrap();
+        assert_eq!(ops, vec![ins(5, "c")]);
+        assert_eq!(from_byte_ops(text, &ops, IndexUnit::Utf16).unwrap(), vec![ins(3, "c")]);
+
+        // the middle of surrogate pair
+        assert_eq!(
+            to_byte_ops(text, &[ins(2, "c")], IndexUnit::Utf16),
+            Err(StrOpError::InvalidIndex(0))
+        );
+    }
+
+    #[test]
+    fn char_and_grapheme() {
+        let text = "e\u{301}x我";
+        let ops = to_byte_ops(text, &[del(1, "\u{301}")], IndexUnit::Char).unwrap();
+        assert_eq!(ops, vec![del(1, "\u{301}")]);
+
+        let ops = to_byte_ops(text, &[del(1, "x"), ins(3, "!")], IndexUnit::Grapheme).unwrap();
+        assert_eq!(ops, vec![del(3, "x"), ins(7, "!")]);
+        assert_eq!(
+            from_byte_ops(text, &ops, IndexUnit::Grapheme).unwrap(),
+            vec![del(1, "x"), ins(3, "!")]
+        );
+        assert_eq!(
+            from_byte_ops(text, &[ins(1, "!")], IndexUnit::Grapheme),
+            Err(StrOpError::InvalidIndex(0))
+        );
+    }
+
+    #[test]
+    fn validate_ops() {
+        let text = "我是程序员";
+        assert_eq!(validate(text, &[ins(1, "!")]), Err(StrOpError::InvalidIndex(0)));
+        assert_eq!(validate(text, &[ins(100, "!")]), Err(StrOpError::InvalidIndex(0)));
+        assert_eq!(validate(text, &[del(3, "我")]), Err(StrOpError::TextMismatch(0)));
+        assert_eq!(
+            validate(text, &[del(3, "是"), ins(3, "!")]),
+            Err(StrOpError::Unordered(1))
+        );
+        assert_eq!(validate(text, &[del(3, "是"), ins(6, "!")]), Ok(()));
+    }
+}
@@ -1,275 +1,983 @@
-use Rundo;
 use difference::{Changeset, Difference};
+use field_path::FieldPath;
 use primitive_type::ValueType;
+use seg::{compose_segs, push_seg, segs_overlap, transform_segs, Seg};
 use std;
-#[derive(PartialEq, Debug)]
+use std::collections::HashMap;
+use std::ops::{Bound, Deref, DerefMut, RangeBounds};
+use string_index::validate;
+use {ChangedPaths, Compose, Invert, MergePolicy, Rundo, Transform};
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
 pub enum StrOP {
-  Ins {
-    idx: usize,
-    value: String,
-  },
-  Del {
-    idx: usize,
-    value: String,
-  },
-  Chg {
-    idx: usize,
-    from: String,
-    to: String,
-  },
+    Ins {
+        idx: usize,
+        value: String,
+    },
+    Del {
+        idx: usize,
+        value: String,
+    },
+    Chg {
+        idx: usize,
+        from: String,
+        to: String,
+    },
 }
 
-impl Rundo for ValueType<String> {
-  type Op = std::vec::Vec<StrOP>;
-
-  fn dirty(&self) -> bool {
-    self.origin.is_some()
-  }
-
-  fn reset(&mut self) {
-    self.origin = None;
-  }
-
-  fn change_op(&mut self) -> Option<Self::Op> {
-    self
-      .origin
-      .as_ref()
-      .map(|ref ori| Changeset::new(ori, &self.value, "").diffs)
-      .map(|diffs| {
-        let mut ops = Vec::with_capacity(diffs.len());
-        let mut base = 0;
-        let mut rem_diff = false;
-        for diff in diffs {
-          let is_rem_diff = std::mem::discriminant(&diff)
+impl StrOP {
+    /// the byte offset in the origin string where this op occurs.
+    pub fn index(&self) -> usize {
+        match self {
+            &StrOP::Ins { idx, .. } | &StrOP::Del { idx, .. } | &StrOP::Chg { idx, .. } => idx,
+        }
+    }
+
+    /// the same op but occurs at `idx`.
+    pub fn with_index(&self, idx: usize) -> StrOP {
+        match self {
+            &StrOP::Ins { ref value, .. } => StrOP::Ins {
+                idx,
+                value: value.to_string(),
+            },
+            &StrOP::Del { ref value, .. } => StrOP::Del {
+                idx,
+                value: value.to_string(),
+            },
+            &StrOP::Chg {
+                ref from, ref to, ..
+            } => StrOP::Chg {
+                idx,
+                from: from.to_string(),
+                to: to.to_string(),
+            },
+        }
+    }
+}
+
+fn to_segs(ops: &[StrOP]) -> Vec<Seg<String>> {
+    let mut segs = Vec::with_capacity(ops.len() * 2);
+    let mut base = 0;
+    for op in ops {
+        match op {
+            &StrOP::Ins { idx, ref value } => {
+                push_seg(&mut segs, Seg::Retain(idx - base));
+                push_seg(&mut segs, Seg::Ins(value.to_string()));
+                base = idx;
+            }
+            &StrOP::Del { idx, ref value } => {
+                push_seg(&mut segs, Seg::Retain(idx - base));
+                push_seg(&mut segs, Seg::Del(value.to_string()));
+                base = idx + value.len();
+            }
+            &StrOP::Chg {
+                idx,
+                ref from,
+                ref to,
+            } => {
+                push_seg(&mut segs, Seg::Retain(idx - base));
+                push_seg(&mut segs, Seg::Del(from.to_string()));
+                push_seg(&mut segs, Seg::Ins(to.to_string()));
+                base = idx + from.len();
+            }
+        }
+    }
+    segs
+}
+
+fn from_segs(segs: Vec<Seg<String>>) -> Vec<StrOP> {
+    let mut ops = Vec::with_capacity(segs.len());
+    let mut base = 0;
+    let mut segs = segs.into_iter().peekable();
+    while let Some(seg) = segs.next() {
+        match seg {
+            Seg::Retain(n) => base += n,
+            Seg::Ins(value) => ops.push(StrOP::Ins { idx: base, value }),
+            Seg::Del(from) => {
+                let idx = base;
+                base += from.len();
+                let to = match segs.peek() {
+                    Some(&Seg::Ins(ref to)) => Some(to.to_string()),
+                    _ => None,
+                };
+                match to {
+                    Some(to) => {
+                        segs.next();
+                        ops.push(StrOP::Chg { idx, from, to });
+                    }
+                    None => ops.push(StrOP::Del { idx, value: from }),
+                }
+            }
+        }
+    }
+    ops
+}
+
+impl Compose for Vec<StrOP> {
+    fn compose(&self, next: &Self) -> Self {
+        from_segs(compose_segs(to_segs(self), to_segs(next)))
+    }
+}
+
+impl Invert for Vec<StrOP> {
+    fn invert(&self) -> Self {
+        self.iter()
+            .scan(0isize, |base, uop| {
+                let uop = match uop {
+                    &StrOP::Ins { idx, ref value } => {
+                        let nidx = (idx as isize) + *base;
+                        *base += value.len() as isize;
+                        StrOP::Del {
+                            idx: nidx as usize,
+                            value: value.to_string(),
+                        }
+                    }
+                    &StrOP::Del { idx, ref value } => {
+                        let nidx = (idx as isize) + *base;
+                        *base -= value.len() as isize;
+                        StrOP::Ins {
+                            idx: nidx as usize,
+                            value: value.to_string(),
+                        }
+                    }
+                    &StrOP::Chg {
+                        idx,
+                        ref from,
+                        ref to,
+                    } => {
+                        let nidx = (idx as isize) + *base;
+                        *base += to.len() as isize - from.len() as isize;
+                        StrOP::Chg {
+                            idx: nidx as usize,
+                            to: from.to_string(),
+                            from: to.to_string(),
+                        }
+                    }
+                };
+                Some(uop)
+            })
+            .collect()
+    }
+}
+
+/// Concurrent string ops are merged, unless they edit the same place and the
+/// policy is `Conflict`.
+impl Transform for Vec<StrOP> {
+    fn transform_by(&self, other: &Self, policy: MergePolicy) -> Option<Self> {
+        let (segs, other) = (to_segs(self), to_segs(other));
+        if policy == MergePolicy::Conflict && segs_overlap(&segs, &other) {
+            return None;
+        }
+        Some(from_segs(transform_segs(segs, other)))
+    }
+}
+
+impl ChangedPaths for Vec<StrOP> {
+    fn changed_paths(&self) -> Vec<FieldPath> {
+        vec![FieldPath::root()]
+    }
+}
+
+fn diffs_to_ops(diffs: Vec<Difference>) -> Vec<StrOP> {
+    let mut ops = Vec::with_capacity(diffs.len());
+    let mut base = 0;
+    let mut rem_diff = false;
+    for diff in diffs {
+        let is_rem_diff = std::mem::discriminant(&diff)
             == std::mem::discriminant(&Difference::Rem("".to_string()));
-          match diff {
+        match diff {
             Difference::Same(text) => base += text.len(),
             Difference::Rem(text) => {
-              let idx = base;
-              base += text.len();
-              ops.push(StrOP::Del { idx, value: text });
+                let idx = base;
+                base += text.len();
+                ops.push(StrOP::Del { idx, value: text });
             }
             Difference::Add(ref text) if rem_diff => {
-              if let Some(StrOP::Del { idx, value }) = ops.pop() {
-                ops.push(StrOP::Chg {
-                  idx,
-                  from: value,
-                  to: text.to_string(),
-                });
-              } else {
-                panic!("must be a Del op here!");
-              }
+                if let Some(StrOP::Del { idx, value }) = ops.pop() {
+                    ops.push(StrOP::Chg {
+                        idx,
+                        from: value,
+                        to: text.to_string(),
+                    });
+                } else {
+                    panic!("must be a Del op here!");
+                }
             }
             Difference::Add(text) => {
-              ops.push(StrOP::Ins {
-                idx: base,
-                value: text,
-              });
+                ops.push(StrOP::Ins {
+                    idx: base,
+                    value: text,
+                });
             }
-          };
-          rem_diff = is_rem_diff;
+        };
+        rem_diff = is_rem_diff;
+    }
+    ops
+}
+
+/// How to split string to compare when generate string ops.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StrDiff {
+    /// compare char by char, generate the most precise ops, it's the default.
+    Char,
+    /// compare word by word, a word include its trailing whitespaces.
+    Word,
+    /// compare line by line, a line include its line break.
+    Line,
+    /// not compare, just replace the whole string.
+    Replace,
+}
+
+fn split_words(text: &str) -> Vec<&str> {
+    let mut words = vec![];
+    let mut start = 0;
+    let mut in_space = false;
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() {
+            in_space = true;
+        } else if in_space {
+            words.push(&text[start..i]);
+            start = i;
+            in_space = false;
         }
-        return ops;
-      })
-  }
-
-  fn back(&mut self, op: &Self::Op) {
-    self.reset();
-    let op: Vec<StrOP> = op.iter()
-      .scan(0isize, |base, uop| {
-        let uop = match uop {
-          &StrOP::Ins { idx, ref value } => {
-            let nidx = (idx as isize) + *base;
-            *base += value.len() as isize;
-            StrOP::Del {
-              idx: nidx as usize,
-              value: value.to_string(),
-            }
-          }
-          &StrOP::Del { idx, ref value } => {
-            let nidx = (idx as isize) + *base;
-            *base -= value.len() as isize;
-            StrOP::Ins {
-              idx: nidx as usize,
-              value: value.to_string(),
+    }
+    if start < text.len() {
+        words.push(&text[start..]);
+    }
+    words
+}
+
+fn split_lines(text: &str) -> Vec<&str> {
+    let mut lines = vec![];
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        if c == '\n' {
+            lines.push(&text[start..i + 1]);
+            start = i + 1;
+        }
+    }
+    if start < text.len() {
+        lines.push(&text[start..]);
+    }
+    lines
+}
+
+/// encode every token as a char, so tokens can be compared by a char diff.
+fn encode_tokens<'a>(
+    tokens: Vec<&'a str>,
+    codes: &mut HashMap<&'a str, char>,
+    dict: &mut Vec<&'a str>,
+) -> String {
+    tokens
+        .into_iter()
+        .map(|token| {
+            if let Some(code) = codes.get(token) {
+                return *code;
             }
-          }
-          &StrOP::Chg {
+            // skip the surrogate code points, which are not valid chars.
+            let n = dict.len() as u32;
+            let n = if n < 0xD800 { n } else { n + 0x800 };
+            let code = std::char::from_u32(n).expect("too many tokens to diff");
+            codes.insert(token, code);
+            dict.push(token);
+            code
+        })
+        .collect()
+}
+
+fn token_diffs(ori: &str, curr: &str, split: fn(&str) -> Vec<&str>) -> Vec<Difference> {
+    let mut codes = HashMap::new();
+    let mut dict = vec![];
+    let ori_code = encode_tokens(split(ori), &mut codes, &mut dict);
+    let curr_code = encode_tokens(split(curr), &mut codes, &mut dict);
+    let decode = |code: String| -> String {
+        code.chars()
+            .map(|c| {
+                let n = c as u32;
+                dict[if n < 0xE000 { n } else { n - 0x800 } as usize]
+            })
+            .collect()
+    };
+
+    Changeset::new(&ori_code, &curr_code, "")
+        .diffs
+        .into_iter()
+        .map(|diff| match diff {
+            Difference::Same(code) => Difference::Same(decode(code)),
+            Difference::Add(code) => Difference::Add(decode(code)),
+            Difference::Rem(code) => Difference::Rem(decode(code)),
+        })
+        .collect()
+}
+
+/// generate the op which change `ori` to `curr`, compared by `diff`.
+pub(crate) fn diff_ops(ori: &str, curr: &str, diff: StrDiff) -> Vec<StrOP> {
+    match diff {
+        StrDiff::Char => diffs_to_ops(Changeset::new(ori, curr, "").diffs),
+        StrDiff::Word => diffs_to_ops(token_diffs(ori, curr, split_words)),
+        StrDiff::Line => diffs_to_ops(token_diffs(ori, curr, split_lines)),
+        StrDiff::Replace => replace_ops(ori, curr),
+    }
+}
+
+fn replace_ops(ori: &str, curr: &str) -> Vec<StrOP> {
+    if ori == curr {
+        vec![]
+    } else if ori.is_empty() {
+        vec![StrOP::Ins {
+            idx: 0,
+            value: curr.to_string(),
+        }]
+    } else if curr.is_empty() {
+        vec![StrOP::Del {
+            idx: 0,
+            value: ori.to_string(),
+        }]
+    } else {
+        vec![StrOP::Chg {
+            idx: 0,
+            from: ori.to_string(),
+            to: curr.to_string(),
+        }]
+    }
+}
+
+/// A String with edit methods, the edits record string ops directly, only the
+/// changes through `DerefMut` need to diff the whole string when generate op.
+#[derive(Clone)]
+pub struct StringType {
+    pub(crate) value: String,
+    pub(crate) origin: Option<String>,
+    pub(crate) edits: Option<Vec<StrOP>>,
+}
+
+/// impl Deref let StringType transparent to user access the String.
+impl Deref for StringType {
+    type Target = String;
+    fn deref(&self) -> &String {
+        &self.value
+    }
+}
+
+/// Modify the String directly, the origin is backed up and the op will be
+/// generated by diff the whole string.
+impl DerefMut for StringType {
+    fn deref_mut(&mut self) -> &mut String {
+        if self.origin.is_none() {
+            self.origin = Some(self.value.clone());
+        }
+        &mut self.value
+    }
+}
+
+impl From<String> for StringType {
+    fn from(from: String) -> Self {
+        StringType {
+            value: from,
+            origin: None,
+            edits: None,
+        }
+    }
+}
+
+impl<'a> From<&'a str> for StringType {
+    fn from(from: &'a str) -> Self {
+        StringType::from(from.to_string())
+    }
+}
+
+impl AsRef<String> for StringType {
+    fn as_ref(&self) -> &String {
+        &self.value
+    }
+}
+
+impl StringType {
+    /// generate the change op like `change_op`, but compare strings split by `diff`.
+    pub fn change_op_by(&self, diff: StrDiff) -> Option<Vec<StrOP>> {
+        if !self.dirty() {
+            return None;
+        }
+        let diffed = self
+            .origin
+            .as_ref()
+            .map(|ori| diff_ops(ori, &self.value, diff));
+        self.edits.compose(&diffed).and_then(non_empty)
+    }
+
+    /// Inserts a string slice at byte position `idx`, like `String::insert_str`.
+    pub fn insert_str(&mut self, idx: usize, string: &str) {
+        self.value.insert_str(idx, string);
+        self.record(StrOP::Ins {
             idx,
-            ref from,
-            ref to,
-          } => {
-            let nidx = (idx as isize) + *base;
-            *base += to.len() as isize - from.len() as isize;
-            StrOP::Chg {
-              idx: nidx as usize,
-              to: from.to_string(),
-              from: to.to_string(),
-            }
-          }
+            value: string.to_string(),
+        });
+    }
+
+    /// Appends a string slice onto the end, like `String::push_str`.
+    pub fn push_str(&mut self, string: &str) {
+        let idx = self.value.len();
+        self.insert_str(idx, string);
+    }
+
+    /// Shortens to the byte length `new_len`, like `String::truncate`.
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len < self.value.len() {
+            let value = self.value[new_len..].to_string();
+            self.value.truncate(new_len);
+            self.record(StrOP::Del {
+                idx: new_len,
+                value,
+            });
+        }
+    }
+
+    /// Replaces the byte range with the string, like `String::replace_range`.
+    pub fn replace_range<R>(&mut self, range: R, string: &str)
+    where
+        R: RangeBounds<usize>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n + 1,
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => self.value.len(),
+        };
+        let from = self.value[start..end].to_string();
+        self.value.replace_range(start..end, string);
+        let op = match (from.is_empty(), string.is_empty()) {
+            (true, true) => return,
+            (true, false) => StrOP::Ins {
+                idx: start,
+                value: string.to_string(),
+            },
+            (false, true) => StrOP::Del {
+                idx: start,
+                value: from,
+            },
+            (false, false) => StrOP::Chg {
+                idx: start,
+                from,
+                to: string.to_string(),
+            },
+        };
+        self.record(op);
+    }
+
+    fn record(&mut self, op: StrOP) {
+        // the origin value will be diffed, include this edit.
+        if self.origin.is_some() {
+            return;
+        }
+        let op = vec![op];
+        self.edits = match self.edits.take() {
+            Some(edits) => non_empty(edits.compose(&op)),
+            None => Some(op),
         };
-        Some(uop)
-      })
-      .collect();
-    self.forward(&op);
-  }
-
-  fn forward(&mut self, op: &Self::Op) {
-    self.reset();
+    }
+}
+
+/// edits composed to nothing are not a change.
+pub(crate) fn non_empty(ops: Vec<StrOP>) -> Option<Vec<StrOP>> {
+    if ops.is_empty() {
+        None
+    } else {
+        Some(ops)
+    }
+}
+
+/// apply `op` to `text`, build the new string in one pass.
+fn apply_ops(text: &str, op: &[StrOP]) -> String {
+    debug_assert_eq!(validate(text, op), Ok(()));
     let realloc_size = op.iter().fold(0, |acc, x| {
-      acc + match x {
-        &StrOP::Ins { ref value, .. } => value.len() as isize,
-        &StrOP::Del { ref value, .. } => -(value.len() as isize),
-        &StrOP::Chg {
-          ref from, ref to, ..
-        } => to.len() as isize - from.len() as isize,
-      }
+        acc + match x {
+            &StrOP::Ins { ref value, .. } => value.len() as isize,
+            &StrOP::Del { ref value, .. } => -(value.len() as isize),
+            &StrOP::Chg {
+                ref from, ref to, ..
+            } => to.len() as isize - from.len() as isize,
+        }
     });
 
     let mut base = 0;
-    let size = self.value.len() as isize + realloc_size;
+    let size = text.len() as isize + realloc_size;
     let mut newstr = String::with_capacity(size as usize);
     for uop in op {
-      match uop {
-        &StrOP::Ins { idx, ref value } => {
-          newstr += &self.value[base..idx];
-          newstr += value;
-          base = idx;
-        }
-        &StrOP::Del { idx, ref value } => {
-          newstr += &self.value[base..idx];
-          base = idx + value.len();
-        }
-        &StrOP::Chg {
-          idx,
-          ref from,
-          ref to,
-        } => {
-          newstr += &self.value[base..idx];
-          base = idx + from.len();
-          newstr += to;
+        match uop {
+            &StrOP::Ins { idx, ref value } => {
+                newstr += &text[base..idx];
+                newstr += value;
+                base = idx;
+            }
+            &StrOP::Del { idx, ref value } => {
+                newstr += &text[base..idx];
+                base = idx + value.len();
+            }
+            &StrOP::Chg {
+                idx,
+                ref from,
+                ref to,
+            } => {
+                newstr += &text[base..idx];
+                base = idx + from.len();
+                newstr += to;
+            }
+        };
+    }
+
+    newstr += &text[base..];
+    newstr
+}
+
+impl Rundo for StringType {
+    type Op = std::vec::Vec<StrOP>;
+
+    /// compare the text with the one before all changes, no diff needed.
+    fn dirty(&self) -> bool {
+        match (&self.origin, &self.edits) {
+            (&Some(ref ori), &Some(ref edits)) => apply_ops(ori, &edits.invert()) != self.value,
+            (&Some(ref ori), &None) => *ori != self.value,
+            (&None, edits) => edits.is_some(),
         }
-      };
     }
 
-    newstr += &self.value[base..];
-    self.value = newstr;
-  }
+    fn reset(&mut self) {
+        self.origin = None;
+        self.edits = None;
+    }
+
+    fn change_op(&mut self) -> Option<Self::Op> {
+        self.change_op_by(StrDiff::Char)
+    }
+
+    fn back(&mut self, op: &Self::Op) {
+        self.forward(&op.invert());
+    }
+
+    fn forward(&mut self, op: &Self::Op) {
+        self.reset();
+        self.value = apply_ops(&self.value, op);
+    }
+}
+
+/// A plain `ValueType<String>` has no edit methods, its op is always diffed
+/// from the origin, use `StringType` to record edits.
+impl Rundo for ValueType<String> {
+    type Op = std::vec::Vec<StrOP>;
+
+    fn dirty(&self) -> bool {
+        self.origin.as_ref().map_or(false, |ori| *ori != self.value)
+    }
+
+    fn reset(&mut self) {
+        self.origin = None;
+    }
+
+    fn change_op(&mut self) -> Option<Self::Op> {
+        self.origin
+            .as_ref()
+            .and_then(|ori| non_empty(diff_ops(ori, &self.value, StrDiff::Char)))
+    }
+
+    fn back(&mut self, op: &Self::Op) {
+        self.forward(&op.invert());
+    }
+
+    fn forward(&mut self, op: &Self::Op) {
+        self.reset();
+        self.value = apply_ops(&self.value, op);
+    }
 }
 
 #[test]
 fn string_ops() {
-  let mut hello = ValueType::<String>::from("hello world!".to_string());
-  *hello = "hello adoo!".to_string();
-  let ops = hello.change_op().unwrap();
-  assert_eq!(
-    ops,
-    vec![
-      StrOP::Chg {
-        idx: 6,
-        from: "w".to_string(),
-        to: "ad".to_string(),
-      },
-      StrOP::Chg {
-        idx: 8,
-        from: "rld".to_string(),
-        to: "o".to_string(),
-      },
-    ]
-  );
-
-  hello.reset();
-  *hello = "hello adoo! by Rust.".to_string();
-  let ops = hello.change_op().unwrap();
-  assert_eq!(
-    ops,
-    vec![
-      StrOP::Ins {
-        idx: 11,
-        value: " by Rust.".to_string(),
-      },
-    ]
-  );
-
-  hello.reset();
-  *hello = "by Rust.".to_string();
-  let ops = hello.change_op().unwrap();
-  assert_eq!(
-    ops,
-    vec![
-      StrOP::Del {
-        idx: 0,
-        value: "hello adoo! ".to_string(),
-      },
-    ]
-  )
+    let mut hello = StringType::from("hello world!".to_string());
+    *hello = "hello adoo!".to_string();
+    let ops = hello.change_op().unwrap();
+    assert_eq!(
+        ops,
+        vec![
+            StrOP::Chg {
+                idx: 6,
+                from: "w".to_string(),
+                to: "ad".to_string(),
+            },
+            StrOP::Chg {
+                idx: 8,
+                from: "rld".to_string(),
+                to: "o".to_string(),
+            },
+        ]
+    );
+
+    hello.reset();
+    *hello = "hello adoo! by Rust.".to_string();
+    let ops = hello.change_op().unwrap();
+    assert_eq!(
+        ops,
+        vec![StrOP::Ins {
+            idx: 11,
+            value: " by Rust.".to_string(),
+        },]
+    );
+
+    hello.reset();
+    *hello = "by Rust.".to_string();
+    let ops = hello.change_op().unwrap();
+    assert_eq!(
+        ops,
+        vec![StrOP::Del {
+            idx: 0,
+            value: "hello adoo! ".to_string(),
+        },]
+    )
 }
 
 #[test]
 fn string_chinese() {
-  let mut chinese = ValueType::<String>::from("我白天是程序员".to_string());
-  *chinese = "晚上是个学生".to_string();
-  let op = chinese.change_op().unwrap();
-  assert_eq!(
-    op,
-    vec![
-      StrOP::Chg {
-        idx: 0,
-        from: "我白天".to_string(),
-        to: "晚上".to_string(),
-      },
-      StrOP::Chg {
-        idx: 12,
-        from: "程序员".to_string(),
-        to: "个学生".to_string(),
-      },
-    ]
-  );
+    let mut chinese = StringType::from("我白天是程序员".to_string());
+    *chinese = "晚上是个学生".to_string();
+    let op = chinese.change_op().unwrap();
+    assert_eq!(
+        op,
+        vec![
+            StrOP::Chg {
+                idx: 0,
+                from: "我白天".to_string(),
+                to: "晚上".to_string(),
+            },
+            StrOP::Chg {
+                idx: 12,
+                from: "程序员".to_string(),
+                to: "个学生".to_string(),
+            },
+        ]
+    );
 
-  chinese.back(&op);
-  assert_eq!(*chinese, "我白天是程序员");
-  chinese.forward(&op);
-  assert_eq!(*chinese, "晚上是个学生");
+    chinese.back(&op);
+    assert_eq!(*chinese, "我白天是程序员");
+    chinese.forward(&op);
+    assert_eq!(*chinese, "晚上是个学生");
 }
 
 #[test]
 fn string_poem() {
-  let one = "You say that you love rain, but you open your umbrella when it rains...";
-  let two = "You say that you love the sun, but you find a shadow spot when the sun shines...";
-  let three = "You say that you love the wind, But you close your windows when wind blows...";
-  let four = "This is why I am afraid; You say that you love me too...";
-  let mut afriad = ValueType::<String>::from(one.to_string());
+    let one = "You say that you love rain, but you open your umbrella when it rains...";
+    let two = "You say that you love the sun, but you find a shadow spot when the sun shines...";
+    let three = "You say that you love the wind, But you close your windows when wind blows...";
+    let four = "This is why I am afraid; You say that you love me too...";
+    let mut afriad = StringType::from(one.to_string());
+
+    *afriad = two.to_string();
+    let op1 = afriad.change_op().unwrap();
+    afriad.reset();
+    assert_eq!(afriad.value, two);
+
+    *afriad = three.to_string();
+    let op2 = afriad.change_op().unwrap();
+    afriad.reset();
+    assert_eq!(afriad.value, three);
+
+    *afriad = four.to_string();
+    let op3 = afriad.change_op().unwrap();
+    afriad.reset();
+    assert_eq!(afriad.value, four);
+
+    afriad.back(&op3);
+    assert_eq!(afriad.value, three);
 
-  *afriad = two.to_string();
-  let op1 = afriad.change_op().unwrap();
-  afriad.reset();
-  assert_eq!(afriad.value, two);
+    afriad.back(&op2);
+    assert_eq!(afriad.value, two);
 
-  *afriad = three.to_string();
-  let op2 = afriad.change_op().unwrap();
-  afriad.reset();
-  assert_eq!(afriad.value, three);
+    afriad.back(&op1);
+    assert_eq!(afriad.value, one);
 
-  *afriad = four.to_string();
-  let op3 = afriad.change_op().unwrap();
-  afriad.reset();
-  assert_eq!(afriad.value, four);
+    afriad.forward(&op1);
+    assert_eq!(afriad.value, two);
 
-  afriad.back(&op3);
-  assert_eq!(afriad.value, three);
+    afriad.forward(&op2);
+    assert_eq!(afriad.value, three);
 
-  afriad.back(&op2);
-  assert_eq!(afriad.value, two);
+    afriad.forward(&op3);
+    assert_eq!(afriad.value, four);
+}
+
+#[test]
+fn string_compose() {
+    let mut text = StringType::from("hello world!".to_string());
+    *text = "hello adoo!".to_string();
+    let op1 = text.change_op().unwrap();
+    text.reset();
+    *text = "hi adoo, by Rust.".to_string();
+    let op2 = text.change_op().unwrap();
+    text.reset();
+
+    let op = op1.compose(&op2);
+    text.back(&op);
+    assert_eq!(*text, "hello world!");
+    text.forward(&op);
+    assert_eq!(*text, "hi adoo, by Rust.");
+
+    let ins = vec![StrOP::Ins {
+        idx: 0,
+        value: "abc".to_string(),
+    }];
+    let del = vec![StrOP::Del {
+        idx: 1,
+        value: "bc".to_string(),
+    }];
+    assert_eq!(
+        ins.compose(&del),
+        vec![StrOP::Ins {
+            idx: 0,
+            value: "a".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn string_transform() {
+    let mut text = StringType::from("hello world!".to_string());
+    *text = "hello, world!".to_string();
+    let comma = text.change_op().unwrap();
+    text.back(&comma);
+    *text = "hello world! by Rust.".to_string();
+    let by = text.change_op().unwrap();
+    text.back(&by);
+
+    text.forward(&by);
+    text.forward(&comma.transform(&by).unwrap());
+    assert_eq!(*text, "hello, world! by Rust.");
+
+    let mut text = StringType::from("hello world!".to_string());
+    text.forward(&comma);
+    text.forward(&by.transform(&comma).unwrap());
+    assert_eq!(*text, "hello, world! by Rust.");
+
+    // both changed the same word, a conflict unless merge them by a policy.
+    let mut text = StringType::from("hello world!".to_string());
+    *text = "hello adoo!".to_string();
+    let adoo = text.change_op().unwrap();
+    text.back(&adoo);
+    *text = "hello rust!".to_string();
+    let rust = text.change_op().unwrap();
+    text.back(&rust);
+
+    assert_eq!(adoo.transform(&rust), None);
+    text.forward(&rust);
+    let rebased = adoo
+        .transform_by(&rust, MergePolicy::LastWriterWins)
+        .unwrap();
+    text.forward(&rebased);
+    let merged = text.to_string();
 
-  afriad.back(&op1);
-  assert_eq!(afriad.value, one);
+    let mut other = StringType::from("hello world!".to_string());
+    other.forward(&adoo);
+    other.forward(
+        &rust
+            .transform_by(&adoo, MergePolicy::FirstWriterWins)
+            .unwrap(),
+    );
+    assert_eq!(*other, merged);
 
-  afriad.forward(&op1);
-  assert_eq!(afriad.value, two);
+    // undo still works after transform.
+    text.back(&rebased);
+    assert_eq!(*text, "hello rust!");
+}
+
+#[test]
+fn string_transform_tie() {
+    let ins = |value: &str| {
+        vec![StrOP::Ins {
+            idx: 5,
+            value: value.to_string(),
+        }]
+    };
+    let del = |idx, value: &str| {
+        vec![StrOP::Del {
+            idx,
+            value: value.to_string(),
+        }]
+    };
 
-  afriad.forward(&op2);
-  assert_eq!(afriad.value, three);
+    let mut text = StringType::from("hello world!".to_string());
+    text.forward(&ins(" b"));
+    text.forward(
+        &ins(" a")
+            .transform_by(&ins(" b"), MergePolicy::LastWriterWins)
+            .unwrap(),
+    );
+    assert_eq!(*text, "hello a b world!");
+
+    let mut text = StringType::from("hello world!".to_string());
+    text.forward(&ins(" a"));
+    text.forward(
+        &ins(" b")
+            .transform_by(&ins(" a"), MergePolicy::LastWriterWins)
+            .unwrap(),
+    );
+    assert_eq!(*text, "hello a b world!");
+
+    // overlap deletions.
+    let mut text = StringType::from("hello world!".to_string());
+    text.forward(&del(0, "hello "));
+    let rebased = del(5, " world")
+        .transform_by(&del(0, "hello "), MergePolicy::LastWriterWins)
+        .unwrap();
+    assert_eq!(rebased, del(0, "world"));
+    text.forward(&rebased);
+    assert_eq!(*text, "!");
+}
+
+#[test]
+fn string_transform_policy() {
+    let ins = |value: &str| {
+        vec![StrOP::Ins {
+            idx: 6,
+            value: value.to_string(),
+        }]
+    };
+    let (a, b) = (ins("brave "), ins("new "));
+
+    // concurrent inserts at the same index.
+    assert_eq!(a.transform_by(&b, MergePolicy::Conflict), None);
+    assert_eq!(b.transform_by(&a, MergePolicy::Conflict), None);
+    for &policy in &[MergePolicy::LastWriterWins, MergePolicy::FirstWriterWins] {
+        let a2 = a.transform_by(&b, policy).unwrap();
+        let b2 = b.transform_by(&a, policy.reverse()).unwrap();
+
+        let mut text = StringType::from("hello world!".to_string());
+        text.forward(&b);
+        text.forward(&a2);
+        let mut other = StringType::from("hello world!".to_string());
+        other.forward(&a);
+        other.forward(&b2);
+        // both inserts are kept, in the same order on both sides.
+        assert_eq!(*text, "hello brave new world!");
+        assert_eq!(*other, *text);
+
+        text.back(&a2);
+        assert_eq!(*text, "hello new world!");
+        other.back(&b2);
+        assert_eq!(*other, "hello brave world!");
+    }
 
-  afriad.forward(&op3);
-  assert_eq!(afriad.value, four);
+    // inserts at different indexes never conflict.
+    let end = vec![StrOP::Ins {
+        idx: 12,
+        value: "!!".to_string(),
+    }];
+    assert_eq!(a.transform_by(&end, MergePolicy::Conflict), Some(a.clone()));
+}
+
+#[test]
+fn string_diff_granularity() {
+    let one = "You say that you love rain,\nbut you open your umbrella\nwhen it rains...";
+    let two = "You say that you love the sun,\nbut you open your umbrella\nwhen the sun shines...";
+    let mut text = StringType::from(one.to_string());
+    *text = two.to_string();
+
+    let words = text.change_op_by(StrDiff::Word).unwrap();
+    assert_eq!(
+        words,
+        vec![
+            StrOP::Chg {
+                idx: 22,
+                from: "rain,\n".to_string(),
+                to: "the sun,\n".to_string(),
+            },
+            StrOP::Chg {
+                idx: 60,
+                from: "it rains...".to_string(),
+                to: "the sun shines...".to_string(),
+            },
+        ]
+    );
+
+    let lines = text.change_op_by(StrDiff::Line).unwrap();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].index(), 0);
+
+    let replace = text.change_op_by(StrDiff::Replace).unwrap();
+    assert_eq!(
+        replace,
+        vec![StrOP::Chg {
+            idx: 0,
+            from: one.to_string(),
+            to: two.to_string(),
+        }]
+    );
+
+    for op in vec![words, lines, replace] {
+        text.back(&op);
+        assert_eq!(*text, one);
+        text.forward(&op);
+        assert_eq!(*text, two);
+    }
+}
+
+#[test]
+fn string_edit() {
+    let mut text = StringType::from("hello world".to_string());
+    text.push_str("!");
+    text.replace_range(6..11, "adoo");
+    text.insert_str(0, "> ");
+    assert_eq!(*text, "> hello adoo!");
+    assert!(text.dirty());
+    assert!(text.origin.is_none());
+
+    let op = text.change_op().unwrap();
+    assert_eq!(
+        op,
+        vec![
+            StrOP::Ins {
+                idx: 0,
+                value: "> ".to_string(),
+            },
+            StrOP::Chg {
+                idx: 6,
+                from: "world".to_string(),
+                to: "adoo!".to_string(),
+            },
+        ]
+    );
+
+    // edit after DerefMut are diffed.
+    text.reset();
+    text.truncate(8);
+    *text += "rust";
+    text.push_str("!");
+    let op = text.change_op().unwrap();
+    text.back(&op);
+    assert_eq!(*text, "> hello adoo!");
+    text.forward(&op);
+    assert_eq!(*text, "> hello rust!");
+}
+
+#[test]
+fn string_edit_cancel() {
+    let mut text = StringType::from("hello".to_string());
+    text.push_str(" world");
+    text.truncate(5);
+    assert!(!text.dirty());
+    assert_eq!(text.change_op(), None);
+
+    *text += "!";
+    text.truncate(5);
+    assert!(!text.dirty());
+    assert_eq!(text.change_op(), None);
+}
+
+#[test]
+fn string_edit_reverted_by_deref_mut() {
+    let mut text = StringType::from("hello".to_string());
+    text.insert_str(0, "> ");
+    text.remove(0);
+    assert!(text.dirty());
+    *text = "hello".to_string();
+    assert!(!text.dirty());
+    assert_eq!(text.change_op_by(StrDiff::Line), None);
 }